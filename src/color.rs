@@ -0,0 +1,295 @@
+use std::io::Write;
+
+/// 24-bit color, independent of the terminal library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+/// Colors the terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorDepth {
+    /// 24-bit RGB.
+    #[value(name = "truecolor")]
+    TrueColor,
+    /// xterm 256 color palette.
+    #[value(name = "256")]
+    Ansi256,
+    /// Basic 16 ANSI colors, e.g. the Linux console.
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Detect from COLORTERM & TERM environment variables.
+    pub fn detect() -> Self {
+        Self::from_env(
+            &std::env::var("COLORTERM").unwrap_or_default(),
+            &std::env::var("TERM").unwrap_or_default(),
+        )
+    }
+
+    /// Truecolor unless the terminal is known to have fewer colors, as most terminals support
+    /// it without saying so. Multiplexers are only truecolor if COLORTERM says so.
+    fn from_env(colorterm: &str, term: &str) -> Self {
+        const ANSI16_TERMS: &[&str] = &["linux", "dumb", "ansi", "vt100", "vt220", "cons25"];
+        if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256") {
+            ColorDepth::Ansi256
+        } else if ANSI16_TERMS.contains(&term)
+            || term.ends_with("-16color")
+            || term.ends_with("-8color")
+            // Multiplexers pass on only the colors their TERM says, e.g. 8 for plain "screen".
+            || term.starts_with("screen")
+            || term.starts_with("tmux")
+        {
+            ColorDepth::Ansi16
+        } else {
+            ColorDepth::TrueColor
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Dither {
+    None,
+    /// Ordered dithering using a 4x4 Bayer matrix.
+    #[default]
+    Bayer,
+    /// Floyd-Steinberg error diffusion.
+    FloydSteinberg,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColorOptions {
+    pub depth: ColorDepth,
+    pub dither: Dither,
+}
+
+/// Color as output to the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermColor {
+    Rgb(u8, u8, u8),
+    /// xterm 256 color palette index.
+    Indexed(u8),
+    /// ANSI color 0 - 15.
+    Ansi(u8),
+}
+
+impl TermColor {
+    pub fn write_fg(&self, mut out: impl Write) -> std::io::Result<()> {
+        match *self {
+            TermColor::Rgb(r, g, b) => write!(out, "\x1B[38;2;{};{};{}m", r, g, b),
+            TermColor::Indexed(i) => write!(out, "\x1B[38;5;{}m", i),
+            TermColor::Ansi(i) if i < 8 => write!(out, "\x1B[{}m", 30 + i),
+            TermColor::Ansi(i) => write!(out, "\x1B[{}m", 90 + i - 8),
+        }
+    }
+
+    pub fn write_bg(&self, mut out: impl Write) -> std::io::Result<()> {
+        match *self {
+            TermColor::Rgb(r, g, b) => write!(out, "\x1B[48;2;{};{};{}m", r, g, b),
+            TermColor::Indexed(i) => write!(out, "\x1B[48;5;{}m", i),
+            TermColor::Ansi(i) if i < 8 => write!(out, "\x1B[{}m", 40 + i),
+            TermColor::Ansi(i) => write!(out, "\x1B[{}m", 100 + i - 8),
+        }
+    }
+}
+
+/// xterm default colors for the 16 ANSI colors.
+const ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// Levels of the 6x6x6 color cube in the xterm 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// RGB value of an xterm 256 color palette index.
+pub fn indexed_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => ANSI16[index as usize],
+        16..=231 => {
+            let i = (index - 16) as usize;
+            [
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[(i / 6) % 6],
+                CUBE_LEVELS[i % 6],
+            ]
+        }
+        _ => [8 + (index - 232) * 10; 3],
+    }
+}
+
+fn distance_squared(a: [f32; 3], b: [u8; 3]) -> f32 {
+    // Weighted so green differences matter more, roughly like perceived brightness.
+    let d = [a[0] - b[0] as f32, a[1] - b[1] as f32, a[2] - b[2] as f32];
+    2.0 * d[0] * d[0] + 4.0 * d[1] * d[1] + 3.0 * d[2] * d[2]
+}
+
+/// Nearest palette color, returns the color & its actual RGB value.
+pub fn quantize_color(color: [f32; 3], depth: ColorDepth) -> (TermColor, [u8; 3]) {
+    let clamped = color.map(|c| c.clamp(0.0, 255.0));
+    match depth {
+        ColorDepth::TrueColor => {
+            let c = clamped.map(|c| c.round() as u8);
+            (TermColor::Rgb(c[0], c[1], c[2]), c)
+        }
+        ColorDepth::Ansi256 => {
+            // The 16 system colors are skipped, as they are often changed by terminal themes.
+            let nearest_level = |c: f32| {
+                (0..6)
+                    .min_by(|a, b| {
+                        (CUBE_LEVELS[*a] as f32 - c)
+                            .abs()
+                            .total_cmp(&(CUBE_LEVELS[*b] as f32 - c).abs())
+                    })
+                    .unwrap()
+            };
+            let cube = clamped.map(nearest_level);
+            let cube_color = cube.map(|i| CUBE_LEVELS[i]);
+            let gray_index = (((clamped[0] + clamped[1] + clamped[2]) / 3.0 - 8.0) / 10.0)
+                .round()
+                .clamp(0.0, 23.0) as u8;
+            let gray = 8 + gray_index * 10;
+            if distance_squared(clamped, [gray; 3]) < distance_squared(clamped, cube_color) {
+                (TermColor::Indexed(232 + gray_index), [gray; 3])
+            } else {
+                let index = 16 + 36 * cube[0] + 6 * cube[1] + cube[2];
+                (TermColor::Indexed(index as u8), cube_color)
+            }
+        }
+        ColorDepth::Ansi16 => {
+            let index = (0..16)
+                .min_by(|a, b| {
+                    distance_squared(clamped, ANSI16[*a])
+                        .total_cmp(&distance_squared(clamped, ANSI16[*b]))
+                })
+                .unwrap();
+            (TermColor::Ansi(index as u8), ANSI16[index])
+        }
+    }
+}
+
+/// Quantize a width x height grid of colors to the color depth.
+pub fn quantize(
+    colors: &[[u8; 3]],
+    width: usize,
+    height: usize,
+    options: &ColorOptions,
+) -> Vec<TermColor> {
+    // https://en.wikipedia.org/wiki/Ordered_dithering
+    #[rustfmt::skip]
+    const BAYER: [[f32; 4]; 4] = [
+        [  0.0,  8.0,  2.0, 10.0 ],
+        [ 12.0,  4.0, 14.0,  6.0 ],
+        [  3.0, 11.0,  1.0,  9.0 ],
+        [ 15.0,  7.0, 13.0,  5.0 ],
+    ];
+
+    // Roughly the distance between palette colors.
+    let spread = match options.depth {
+        ColorDepth::TrueColor => return quantize_plain(colors, options.depth),
+        ColorDepth::Ansi256 => 40.0,
+        ColorDepth::Ansi16 => 128.0,
+    };
+
+    match options.dither {
+        Dither::None => quantize_plain(colors, options.depth),
+        Dither::Bayer => colors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let (x, y) = (i % width, i / width);
+                let offset = (BAYER[y % 4][x % 4] / 16.0 - 0.5) * spread;
+                quantize_color(c.map(|c| c as f32 + offset), options.depth).0
+            })
+            .collect(),
+        Dither::FloydSteinberg => {
+            // https://en.wikipedia.org/wiki/Floyd%E2%80%93Steinberg_dithering
+            let mut errors: Vec<[f32; 3]> = colors.iter().map(|c| c.map(|c| c as f32)).collect();
+            let mut result = Vec::with_capacity(colors.len());
+            for y in 0..height {
+                for x in 0..width {
+                    let color = errors[x + y * width];
+                    let (term_color, actual) = quantize_color(color, options.depth);
+                    result.push(term_color);
+                    let error = [0, 1, 2].map(|i| color[i] - actual[i] as f32);
+                    let mut spread_error = |dx: isize, dy: usize, weight: f32| {
+                        let nx = x as isize + dx;
+                        if nx >= 0 && (nx as usize) < width && y + dy < height {
+                            let e = &mut errors[nx as usize + (y + dy) * width];
+                            (0..3).for_each(|i| e[i] += error[i] * weight);
+                        }
+                    };
+                    spread_error(1, 0, 7.0 / 16.0);
+                    spread_error(-1, 1, 3.0 / 16.0);
+                    spread_error(0, 1, 5.0 / 16.0);
+                    spread_error(1, 1, 1.0 / 16.0);
+                }
+            }
+            result
+        }
+    }
+}
+
+fn quantize_plain(colors: &[[u8; 3]], depth: ColorDepth) -> Vec<TermColor> {
+    colors
+        .iter()
+        .map(|c| quantize_color(c.map(|c| c as f32), depth).0)
+        .collect()
+}
+
+#[test]
+fn color_tests() {
+    assert_eq!(ColorDepth::from_env("", "xterm"), ColorDepth::TrueColor);
+    assert_eq!(ColorDepth::from_env("", "screen"), ColorDepth::Ansi16);
+    assert_eq!(ColorDepth::from_env("", "tmux"), ColorDepth::Ansi16);
+    assert_eq!(
+        ColorDepth::from_env("", "tmux-256color"),
+        ColorDepth::Ansi256
+    );
+    assert_eq!(
+        ColorDepth::from_env("truecolor", "tmux"),
+        ColorDepth::TrueColor
+    );
+    assert_eq!(
+        ColorDepth::from_env("", "xterm-256color"),
+        ColorDepth::Ansi256
+    );
+    assert_eq!(ColorDepth::from_env("", "linux"), ColorDepth::Ansi16);
+
+    assert_eq!(
+        quantize_color([255.0, 0.0, 0.0], ColorDepth::Ansi256).0,
+        TermColor::Indexed(196)
+    );
+    assert_eq!(
+        quantize_color([128.0, 128.0, 128.0], ColorDepth::Ansi256).0,
+        TermColor::Indexed(244)
+    );
+    assert_eq!(
+        quantize_color([250.0, 10.0, 10.0], ColorDepth::Ansi16).0,
+        TermColor::Ansi(9)
+    );
+
+    // Dark gray is dithered between black & gray.
+    let options = ColorOptions {
+        depth: ColorDepth::Ansi16,
+        dither: Dither::FloydSteinberg,
+    };
+    let dithered = quantize(&[[64, 64, 64]; 16], 4, 4, &options);
+    assert!(dithered.contains(&TermColor::Ansi(0)) && dithered.contains(&TermColor::Ansi(8)));
+}
@@ -2,44 +2,212 @@
 
 use std::io::Write;
 
-use termion::raw::IntoRawMode;
+use crate::{
+    color::{self, ColorOptions, Rgb, TermColor},
+    terminal,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Cell {
-    bg: termion::color::Rgb,
-    fg: termion::color::Rgb,
+    bg: Rgb,
+    fg: Rgb,
     c: char,
+    /// Opacity of the background when composited onto lower layers, glyphs are always opaque.
+    alpha: f32,
 }
 
 impl Cell {
-    pub const fn new(bg: termion::color::Rgb, fg: termion::color::Rgb, c: char) -> Self {
-        Self { bg, fg, c }
+    /// Empty layer cell.
+    pub const TRANSPARENT: Cell = Cell::new_bg(Rgb(0, 0, 0)).with_alpha(0.0);
+
+    pub const fn new(bg: Rgb, fg: Rgb, c: char) -> Self {
+        Self {
+            bg,
+            fg,
+            c,
+            alpha: 1.0,
+        }
     }
 
-    pub const fn new_bg(bg: termion::color::Rgb) -> Self {
+    pub const fn new_bg(bg: Rgb) -> Self {
         Self {
             bg,
-            fg: termion::color::Rgb(255, 255, 255),
+            fg: Rgb(255, 255, 255),
             c: ' ',
+            alpha: 1.0,
         }
     }
+
+    pub const fn with_alpha(self, alpha: f32) -> Self {
+        Self { alpha, ..self }
+    }
+
+    pub fn bg(&self) -> Rgb {
+        self.bg
+    }
+
+    pub fn fg(&self) -> Rgb {
+        self.fg
+    }
+
+    pub fn c(&self) -> char {
+        self.c
+    }
 }
 
 impl Default for Cell {
     fn default() -> Self {
         Self {
-            bg: termion::color::Rgb(0, 0, 0),
-            fg: termion::color::Rgb(255, 255, 255),
+            bg: Rgb(0, 0, 0),
+            fg: Rgb(255, 255, 255),
             c: ' ',
+            alpha: 1.0,
+        }
+    }
+}
+
+/// How layer colors are combined with the colors below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Add,
+    Multiply,
+    Screen,
+}
+
+impl BlendMode {
+    fn blend(&self, lower: Rgb, upper: Rgb) -> Rgb {
+        let f = |l: u8, u: u8| match self {
+            BlendMode::Normal => u,
+            BlendMode::Add => l.saturating_add(u),
+            BlendMode::Multiply => (l as u16 * u as u16 / 255) as u8,
+            BlendMode::Screen => 255 - ((255 - l) as u16 * (255 - u) as u16 / 255) as u8,
+        };
+        Rgb(
+            f(lower.0, upper.0),
+            f(lower.1, upper.1),
+            f(lower.2, upper.2),
+        )
+    }
+}
+
+fn mix(a: Rgb, b: Rgb, t: f32) -> Rgb {
+    let f = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Rgb(f(a.0, b.0), f(a.1, b.1), f(a.2, b.2))
+}
+
+/// Named layer, composited on top of the display cells & the layers below it.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub blend: BlendMode,
+    /// Multiplies the alpha of every cell, including glyphs.
+    pub opacity: f32,
+    pub visible: bool,
+    pub display: Display,
+}
+
+impl Layer {
+    pub fn new(name: &str, width: usize, height: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            blend: BlendMode::Normal,
+            opacity: 1.0,
+            visible: true,
+            display: Display::init_with_size(&Cell::TRANSPARENT, width, height),
+        }
+    }
+
+    /// Make every cell transparent, so the layer can be redrawn.
+    pub fn clear(&mut self) {
+        self.display.cells.fill(Cell::TRANSPARENT);
+    }
+
+    pub fn drawer(&mut self) -> Drawer<'_> {
+        Drawer::new(&mut self.display)
+    }
+
+    fn composite(&self, lower: &Cell, upper: &Cell) -> Cell {
+        let alpha = (upper.alpha * self.opacity).clamp(0.0, 1.0);
+        let bg = mix(lower.bg, self.blend.blend(lower.bg, upper.bg), alpha);
+        if upper.c != ' ' {
+            Cell {
+                bg,
+                fg: mix(bg, upper.fg, self.opacity.clamp(0.0, 1.0)),
+                c: upper.c,
+                alpha: lower.alpha.max(alpha),
+            }
+        } else {
+            // Glyphs below are tinted by the layer background.
+            Cell {
+                bg,
+                fg: mix(lower.fg, self.blend.blend(lower.fg, upper.bg), alpha),
+                c: lower.c,
+                alpha: lower.alpha.max(alpha),
+            }
+        }
+    }
+}
+
+/// How framebuffer samples are packed into terminal cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DisplayMode {
+    /// 1 sample per cell, using the cell background.
+    #[default]
+    Cell,
+    /// 2 vertical samples per cell, using "▀" with the foreground as the top sample.
+    HalfBlock,
+    /// 2x4 on/off samples per cell using braille patterns, with a single foreground color.
+    Braille,
+    /// 1 sample per cell, using a character ramp of the sample luminance.
+    Ascii,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsciiOptions {
+    /// Characters from darkest to brightest.
+    pub ramp: Vec<char>,
+    /// Use "/ \ | -" on edges, from the luminance gradient.
+    pub edges: bool,
+    /// Keep the sample color as foreground.
+    pub color: bool,
+}
+
+impl Default for AsciiOptions {
+    fn default() -> Self {
+        Self {
+            ramp: " .:-=+*#%@".chars().collect(),
+            edges: false,
+            color: true,
+        }
+    }
+}
+
+fn luminance(color: Rgb) -> f32 {
+    (0.2126 * color.0 as f32 + 0.7152 * color.1 as f32 + 0.0722 * color.2 as f32) / 255.0
+}
+
+impl DisplayMode {
+    /// Framebuffer samples per cell (horizontal, vertical).
+    pub fn samples(&self) -> (usize, usize) {
+        match self {
+            DisplayMode::Cell => (1, 1),
+            DisplayMode::HalfBlock => (1, 2),
+            DisplayMode::Braille => (2, 4),
+            DisplayMode::Ascii => (1, 1),
         }
     }
 }
 
 /// Terminal display
+#[derive(Debug, Clone)]
 pub struct Display {
     width: usize,
     height: usize,
     cells: Box<[Cell]>,
+    /// Layers on top of the cells, from bottom to top.
+    layers: Vec<Layer>,
 }
 
 impl Display {
@@ -48,15 +216,21 @@ impl Display {
             width,
             height,
             cells: vec![*fill; width * height].into_boxed_slice(),
+            layers: Vec::new(),
         }
     }
 
     pub fn init(fill: &Cell) -> std::io::Result<Self> {
-        let (width, height) = termion::terminal_size()?;
-        let (width, height) = (width as usize, height as usize);
+        let (width, height) = terminal::size()?;
         Ok(Self::init_with_size(fill, width, height))
     }
 
+    /// Set every cell & remove all layers, keeping the allocation.
+    pub fn fill(&mut self, fill: &Cell) {
+        self.cells.fill(*fill);
+        self.layers.clear();
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -89,36 +263,306 @@ impl Display {
         &mut self.cells[x + y * self.width]
     }
 
-    pub fn display(&self, mut stdout: impl std::io::Write) -> std::io::Result<()> {
-        // This can be optimized a few ways, because currently it's pretty slow.
-        // Probably best is to check if fg matches previous and dont change if it does.
-        let mut last = Cell::new(
-            termion::color::Rgb(0, 0, 0),
-            termion::color::Rgb(0, 0, 0),
-            ' ',
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Layer by name, a transparent layer is added on top if it doesn't exist.
+    pub fn layer_mut(&mut self, name: &str) -> &mut Layer {
+        match self.layers.iter().position(|layer| layer.name == name) {
+            Some(i) => &mut self.layers[i],
+            None => {
+                self.layers.push(Layer::new(name, self.width, self.height));
+                self.layers.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Add layer on top, replacing a layer with the same name.
+    pub fn push_layer(&mut self, layer: Layer) {
+        self.remove_layer(&layer.name);
+        self.layers.push(layer);
+    }
+
+    pub fn remove_layer(&mut self, name: &str) -> Option<Layer> {
+        let i = self.layers.iter().position(|layer| layer.name == name)?;
+        Some(self.layers.remove(i))
+    }
+
+    /// Flatten the visible layers onto the cells.
+    pub fn composite(&self) -> Display {
+        let mut composited = Display::init_with_size(&Cell::default(), self.width, self.height);
+        composited.cells.copy_from_slice(&self.cells);
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            let layer_display = layer.display.composite();
+            for y in 0..self.height.min(layer_display.height) {
+                for x in 0..self.width.min(layer_display.width) {
+                    let cell = composited.get_mut_unchecked(x, y);
+                    *cell = layer.composite(cell, layer_display.get_unchecked(x, y));
+                }
+            }
+        }
+        composited
+    }
+
+    /// Framebuffer as an image, using the cell backgrounds as pixels.
+    pub fn to_rgb_image(&self) -> image::RgbImage {
+        image::RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let bg = self.get_unchecked(x as usize, y as usize).bg;
+            image::Rgb([bg.0, bg.1, bg.2])
+        })
+    }
+
+    /// Framebuffer as an image, using the cell backgrounds as pixels & the cell alpha as alpha.
+    pub fn to_rgba_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let cell = self.get_unchecked(x as usize, y as usize);
+            let alpha = (cell.alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+            image::Rgba([cell.bg.0, cell.bg.1, cell.bg.2, alpha])
+        })
+    }
+
+    /// Pack a framebuffer, where each cell background is a sample, into terminal cells.
+    /// Samples matching the background are treated as empty by on/off modes.
+    pub fn pack(&self, mode: DisplayMode, background: Rgb, ascii: &AsciiOptions) -> Display {
+        let (sx, sy) = mode.samples();
+        let width = self.width.div_ceil(sx);
+        let height = self.height.div_ceil(sy);
+        let sample = |x: usize, y: usize| self.get(x, y).map(|cell| cell.bg).unwrap_or(background);
+
+        let mut packed = Display::init_with_size(&Cell::default(), width, height);
+        for y in 0..height {
+            for x in 0..width {
+                *packed.get_mut_unchecked(x, y) = match mode {
+                    DisplayMode::Cell => *self.get_unchecked(x, y),
+                    DisplayMode::HalfBlock => {
+                        Cell::new(sample(x, y * 2 + 1), sample(x, y * 2), '▀')
+                    }
+                    DisplayMode::Braille => {
+                        // https://en.wikipedia.org/wiki/Braille_Patterns#Identifying,_naming_and_ordering
+                        const DOTS: [[u32; 2]; 4] =
+                            [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                        let mut dots = 0;
+                        let mut sum = [0u32; 3];
+                        let mut count = 0;
+                        for (dy, row) in DOTS.iter().enumerate() {
+                            for (dx, dot) in row.iter().enumerate() {
+                                let color = sample(x * 2 + dx, y * 4 + dy);
+                                if color != background {
+                                    dots |= dot;
+                                    sum[0] += color.0 as u32;
+                                    sum[1] += color.1 as u32;
+                                    sum[2] += color.2 as u32;
+                                    count += 1;
+                                }
+                            }
+                        }
+                        let fg = match count {
+                            0 => background,
+                            _ => Rgb(
+                                (sum[0] / count) as u8,
+                                (sum[1] / count) as u8,
+                                (sum[2] / count) as u8,
+                            ),
+                        };
+                        Cell::new(background, fg, char::from_u32(0x2800 + dots).unwrap())
+                    }
+                    DisplayMode::Ascii => {
+                        let color = sample(x, y);
+                        let l = |x: usize, y: usize| luminance(sample(x, y));
+                        // Border samples don't have all the neighbors the operator needs.
+                        let inside = x > 0 && y > 0 && x + 1 < width && y + 1 < height;
+                        let edge = if ascii.edges && inside {
+                            // https://en.wikipedia.org/wiki/Sobel_operator
+                            #[rustfmt::skip]
+                            let gx = (l(x + 1, y - 1) + 2.0 * l(x + 1, y) + l(x + 1, y + 1))
+                                   - (l(x - 1, y - 1) + 2.0 * l(x - 1, y) + l(x - 1, y + 1));
+                            #[rustfmt::skip]
+                            let gy = (l(x - 1, y + 1) + 2.0 * l(x, y + 1) + l(x + 1, y + 1))
+                                   - (l(x - 1, y - 1) + 2.0 * l(x, y - 1) + l(x + 1, y - 1));
+                            // Edges run perpendicular to the gradient.
+                            (f32::sqrt(gx * gx + gy * gy) > 1.0).then(|| {
+                                let angle = f32::atan2(gy, gx).to_degrees() + 22.5;
+                                ['|', '/', '-', '\\'][(angle.rem_euclid(180.0) / 45.0) as usize % 4]
+                            })
+                        } else {
+                            None
+                        };
+                        let c = edge.unwrap_or_else(|| {
+                            if ascii.ramp.is_empty() || color == background {
+                                ' '
+                            } else {
+                                let index = luminance(color) * (ascii.ramp.len() - 1) as f32;
+                                ascii.ramp[index.round() as usize]
+                            }
+                        });
+                        if ascii.color {
+                            Cell::new(background, color, c)
+                        } else {
+                            Cell {
+                                c,
+                                ..Cell::default()
+                            }
+                        }
+                    }
+                };
+            }
+        }
+        packed
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PresentedCell {
+    c: char,
+    fg: Option<TermColor>,
+    bg: Option<TermColor>,
+}
+
+/// Writes displays to the terminal, only writing the cells that changed since the previously
+/// presented frame.
+#[derive(Debug)]
+pub struct Presenter {
+    /// No colors are output if not set.
+    colors: Option<ColorOptions>,
+    previous: Option<(usize, usize, Vec<PresentedCell>)>,
+}
+
+impl Presenter {
+    pub fn new(colors: Option<ColorOptions>) -> Self {
+        Self {
+            colors,
+            previous: None,
+        }
+    }
+
+    /// Force the next frame to be fully redrawn.
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    fn presented_cells(&self, display: &Display) -> Vec<PresentedCell> {
+        let Some(colors) = &self.colors else {
+            return display
+                .cells
+                .iter()
+                .map(|cell| PresentedCell {
+                    c: cell.c,
+                    fg: None,
+                    bg: None,
+                })
+                .collect();
+        };
+        let rgb = |c: Rgb| [c.0, c.1, c.2];
+        let fg = color::quantize(
+            &display.cells.iter().map(|c| rgb(c.fg)).collect::<Vec<_>>(),
+            display.width,
+            display.height,
+            colors,
+        );
+        let bg = color::quantize(
+            &display.cells.iter().map(|c| rgb(c.bg)).collect::<Vec<_>>(),
+            display.width,
+            display.height,
+            colors,
         );
-        write!(
-            stdout,
-            "{}{}",
-            termion::color::Fg(last.fg),
-            termion::color::Bg(last.bg),
-        )?;
-        for y in 0..self.height {
-            write!(stdout, "{}", termion::cursor::Goto(1, y as u16 + 1))?;
-            for x in 0..self.width {
-                let cell = self.get_unchecked(x, y);
-                if cell.fg != last.fg {
-                    write!(stdout, "{}", termion::color::Fg(cell.fg))?;
+        display
+            .cells
+            .iter()
+            .zip(fg.into_iter().zip(bg))
+            .map(|(cell, (fg, bg))| PresentedCell {
+                c: cell.c,
+                fg: Some(fg),
+                bg: Some(bg),
+            })
+            .collect()
+    }
+
+    /// Returns the amount of bytes written.
+    pub fn present(
+        &mut self,
+        display: &Display,
+        mut stdout: impl std::io::Write,
+    ) -> std::io::Result<usize> {
+        let composited;
+        let display = if display.layers.is_empty() {
+            display
+        } else {
+            composited = display.composite();
+            &composited
+        };
+        let cells = self.presented_cells(display);
+        let previous = match self.previous.take() {
+            Some((width, height, previous))
+                if width == display.width && height == display.height =>
+            {
+                Some(previous)
+            }
+            _ => None,
+        };
+
+        // Write everything to a buffer first, so the frame is output all at once.
+        let mut out: Vec<u8> = Vec::new();
+        if previous.is_none() {
+            terminal::clear_all(&mut out)?;
+        }
+
+        // Cursor position is unknown at the start of the frame & after writing to the last
+        // column, as terminals differ in when they wrap.
+        let mut cursor: Option<(usize, usize)> = None;
+        let mut fg: Option<TermColor> = None;
+        let mut bg: Option<TermColor> = None;
+
+        for y in 0..display.height {
+            for x in 0..display.width {
+                let i = x + y * display.width;
+                let cell = cells[i];
+                if previous.as_ref().is_some_and(|p| p[i] == cell) {
+                    continue;
+                }
+
+                if cursor != Some((x, y)) {
+                    // Pick the shortest cursor movement.
+                    let goto = format!("\x1B[{};{}H", y + 1, x + 1);
+                    let movement = match cursor {
+                        Some((cx, cy)) if cy == y && cx < x => {
+                            let gap = &cells[cx + y * display.width..i];
+                            // Rewriting unchanged cells can be shorter than moving the cursor.
+                            let rewrite_len = gap.iter().map(|c| c.c.len_utf8()).sum::<usize>();
+                            let forward = format!("\x1B[{}C", x - cx);
+                            if gap.iter().all(|c| c.fg == fg && c.bg == bg)
+                                && rewrite_len <= forward.len().min(goto.len())
+                            {
+                                gap.iter().map(|c| c.c).collect()
+                            } else if forward.len() < goto.len() {
+                                forward
+                            } else {
+                                goto
+                            }
+                        }
+                        Some((_, cy)) if cy + 1 == y && x == 0 => "\r\n".to_string(),
+                        _ => goto,
+                    };
+                    out.extend_from_slice(movement.as_bytes());
+                }
+
+                if let Some(cell_fg) = cell.fg.filter(|c| Some(*c) != fg) {
+                    cell_fg.write_fg(&mut out)?;
+                    fg = Some(cell_fg);
                 }
-                if cell.bg != last.bg {
-                    write!(stdout, "{}", termion::color::Bg(cell.bg))?;
+                if let Some(cell_bg) = cell.bg.filter(|c| Some(*c) != bg) {
+                    cell_bg.write_bg(&mut out)?;
+                    bg = Some(cell_bg);
                 }
-                write!(stdout, "{}", cell.c)?;
-                last = *cell;
+                write!(out, "{}", cell.c)?;
+                cursor = (x + 1 < display.width).then_some((x + 1, y));
             }
         }
+
+        stdout.write_all(&out)?;
         stdout.flush()?;
-        Ok(())
+        self.previous = Some((display.width, display.height, cells));
+        Ok(out.len())
     }
 }
 
@@ -153,14 +597,7 @@ impl<'a> Drawer<'a> {
         }
     }
 
-    pub fn text(
-        &mut self,
-        x: isize,
-        y: isize,
-        text: &str,
-        bg: Option<termion::color::Rgb>,
-        fg: Option<termion::color::Rgb>,
-    ) {
+    pub fn text(&mut self, x: isize, y: isize, text: &str, bg: Option<Rgb>, fg: Option<Rgb>) {
         let mut dx = 0;
         let mut dy = 0;
         text.lines().for_each(|line| {
@@ -170,7 +607,8 @@ impl<'a> Drawer<'a> {
                         cell.fg = fg
                     }
                     if let Some(bg) = bg {
-                        cell.bg = bg
+                        cell.bg = bg;
+                        cell.alpha = 1.0;
                     }
                     cell.c = char;
                 }
@@ -186,60 +624,303 @@ impl<'a> Drawer<'a> {
         });
     }
 
-    fn line_low(&mut self, cell: &Cell, x0: isize, y0: isize, x1: isize, y1: isize) {
-        let dx = x1 - x0;
-        let mut dy = y1 - y0;
-        let mut yi = 1;
-        if dy < 0 {
-            yi = -1;
-            dy = -dy;
+    /// Blend a color into the cell background by coverage, for anti-aliasing.
+    pub fn blend_pixel(&mut self, color: Rgb, x: isize, y: isize, coverage: f32) {
+        let coverage = coverage.clamp(0.0, 1.0);
+        if let Some(cell) = self.get_mut(x, y) {
+            if cell.alpha == 0.0 {
+                // Transparent layer cells take the color, with the coverage as alpha.
+                cell.bg = color;
+            } else {
+                cell.bg = mix(cell.bg, color, coverage);
+            }
+            cell.alpha = cell.alpha.max(coverage);
         }
-        let mut d = (2 * dy) - dx;
-        let mut y = y0;
-        for x in x0..=x1 {
-            self.pixel(cell, x, y);
-            if d > 0 {
-                y += yi;
-                d += 2 * (dy - dx);
+    }
+
+    /// Clip line to the display, expanded by margin, using the Liang-Barsky algorithm.
+    /// https://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm
+    fn clip_line(
+        &self,
+        (x0, y0): (f32, f32),
+        (x1, y1): (f32, f32),
+        margin: f32,
+    ) -> Option<((f32, f32), (f32, f32))> {
+        let (min_x, min_y) = (-margin, -margin);
+        let max_x = self.width() as f32 - 1.0 + margin;
+        let max_y = self.height() as f32 - 1.0 + margin;
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let mut t0: f32 = 0.0;
+        let mut t1: f32 = 1.0;
+        for (p, q) in [
+            (-dx, x0 - min_x),
+            (dx, max_x - x0),
+            (-dy, y0 - min_y),
+            (dy, max_y - y0),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
             } else {
-                d += 2 * dy;
+                let t = q / p;
+                if p < 0.0 {
+                    t0 = t0.max(t);
+                } else {
+                    t1 = t1.min(t);
+                }
             }
         }
+        (t0 <= t1).then_some(((x0 + t0 * dx, y0 + t0 * dy), (x0 + t1 * dx, y0 + t1 * dy)))
     }
 
+    /// Points of a line, including both ends, clipped to the display.
     pub fn iter_line(
-        &mut self,
-        mut x0: isize,
-        mut y0: isize,
+        &self,
+        x0: isize,
+        y0: isize,
         x1: isize,
         y1: isize,
     ) -> impl Iterator<Item = (isize, isize)> {
-        // FIXME: This does not work.
-        std::iter::empty()
-        // https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
-        //let dx = isize::abs(x1 - x0);
-        //let sx = if x0 < x1 { 1 } else { -1 };
-        //let dy = isize::abs(y1 - y0);
-        //let sy = if y0 < y1 { 1 } else { -1 };
-        //let mut error = dx + dy;
-        //
-        //std::iter::from_fn(move || {
-        //    if x0 == x1 && y0 == y1 {
-        //        return None;
-        //    }
-        //    let e2 = 2 * error;
-        //    if e2 >= dy {
-        //        error += dy;
-        //        x0 += sx;
-        //    }
-        //    if e2 <= dx {
-        //        error += dx;
-        //        y0 += sy;
-        //    }
-        //    Some((x0, y0))
-        //})
+        // Only clip ends outside of the display, so lines inside keep their exact pixels.
+        let inside = |x: isize, y: isize| x >= 0 && y >= 0 && x < self.width() && y < self.height();
+        let clipped = if inside(x0, y0) && inside(x1, y1) {
+            Some(((x0, y0), (x1, y1)))
+        } else {
+            self.clip_line((x0 as f32, y0 as f32), (x1 as f32, y1 as f32), 0.0)
+                .map(|((x0, y0), (x1, y1))| {
+                    (
+                        (x0.round() as isize, y0.round() as isize),
+                        (x1.round() as isize, y1.round() as isize),
+                    )
+                })
+        };
+        let (width, height) = (self.width(), self.height());
+        let ((mut x0, mut y0), (x1, y1), mut done) = match clipped {
+            Some((p0, p1)) => (p0, p1, false),
+            None => ((0, 0), (0, 0), true),
+        };
+
+        // https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm#All_cases
+        let dx = isize::abs(x1 - x0);
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let dy = -isize::abs(y1 - y0);
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let point = (x0, y0);
+            if x0 == x1 && y0 == y1 {
+                done = true;
+            }
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x0 += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y0 += sy;
+            }
+            Some(point)
+        })
+        .filter(move |(x, y)| *x >= 0 && *y >= 0 && *x < width && *y < height)
+    }
+
+    pub fn line(&mut self, cell: &Cell, x0: isize, y0: isize, x1: isize, y1: isize) {
+        for (x, y) in self.iter_line(x0, y0, x1, y1) {
+            self.pixel(cell, x, y);
+        }
+    }
+
+    /// Anti-aliased line using Xiaolin Wu's algorithm.
+    /// https://en.wikipedia.org/wiki/Xiaolin_Wu%27s_line_algorithm
+    pub fn line_aa(&mut self, color: Rgb, x0: f32, y0: f32, x1: f32, y1: f32) {
+        let Some(((mut x0, mut y0), (mut x1, mut y1))) = self.clip_line((x0, y0), (x1, y1), 1.0)
+        else {
+            return;
+        };
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let gradient = if x1 == x0 { 1.0 } else { (y1 - y0) / (x1 - x0) };
+
+        let mut plot = |x: f32, y: f32, coverage: f32| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            self.blend_pixel(color, x as isize, y as isize, coverage);
+        };
+
+        // f32::fract is negative for negative values, which the clip margin lets through.
+        let fract = |v: f32| v - v.floor();
+
+        // Ends are weighted by how much of the pixel they cover, the start covers the part
+        // after x & the stop the part before it.
+        let mut end = |x: f32, y: f32, start: bool| {
+            let x_end = x.round();
+            let y_end = y + gradient * (x_end - x);
+            let x_gap = if start {
+                1.0 - fract(x + 0.5)
+            } else {
+                fract(x + 0.5)
+            };
+            plot(x_end, y_end.floor(), (1.0 - fract(y_end)) * x_gap);
+            plot(x_end, y_end.floor() + 1.0, fract(y_end) * x_gap);
+            (x_end, y_end)
+        };
+        let (x_start, y_start) = end(x0, y0, true);
+        let (x_stop, _) = end(x1, y1, false);
+
+        let mut y = y_start + gradient;
+        let mut x = x_start + 1.0;
+        while x < x_stop {
+            plot(x, y.floor(), 1.0 - fract(y));
+            plot(x, y.floor() + 1.0, fract(y));
+            y += gradient;
+            x += 1.0;
+        }
+    }
+
+    /// Line with a width in pixels, drawn as a filled quad with flat ends.
+    pub fn thick_line(
+        &mut self,
+        cell: &Cell,
+        x0: isize,
+        y0: isize,
+        x1: isize,
+        y1: isize,
+        width: f32,
+    ) {
+        let (dx, dy) = ((x1 - x0) as f32, (y1 - y0) as f32);
+        let length = (dx * dx + dy * dy).sqrt();
+        if width <= 1.0 || length == 0.0 {
+            self.line(cell, x0, y0, x1, y1);
+            return;
+        }
+        // Offset to pixel centers & extend the ends by half a pixel so they are covered.
+        let (ux, uy) = (dx / length, dy / length);
+        let (nx, ny) = (-uy * width / 2.0, ux * width / 2.0);
+        let (ax, ay) = (x0 as f32 + 0.5 - ux * 0.5, y0 as f32 + 0.5 - uy * 0.5);
+        let (bx, by) = (x1 as f32 + 0.5 + ux * 0.5, y1 as f32 + 0.5 + uy * 0.5);
+        self.fill_polygon_f32(
+            cell,
+            &[
+                (ax + nx, ay + ny),
+                (bx + nx, by + ny),
+                (bx - nx, by - ny),
+                (ax - nx, ay - ny),
+            ],
+        );
+    }
+
+    /// Rectangle outline, including both corners.
+    pub fn rect(&mut self, cell: &Cell, x0: isize, y0: isize, x1: isize, y1: isize) {
+        self.line(cell, x0, y0, x1, y0);
+        self.line(cell, x1, y0, x1, y1);
+        self.line(cell, x1, y1, x0, y1);
+        self.line(cell, x0, y1, x0, y0);
+    }
+
+    /// Filled rectangle, including both corners.
+    pub fn fill_rect(&mut self, cell: &Cell, x0: isize, y0: isize, x1: isize, y1: isize) {
+        for (x, y) in self.iter_rect(x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)) {
+            self.pixel(cell, x, y);
+        }
     }
 
+    /// Circle outline using the midpoint circle algorithm.
+    /// https://en.wikipedia.org/wiki/Midpoint_circle_algorithm
+    pub fn circle(&mut self, cell: &Cell, cx: isize, cy: isize, radius: isize) {
+        if radius < 0 {
+            return;
+        }
+        let (mut x, mut y) = (radius, 0);
+        let mut error = 1 - radius;
+        while x >= y {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.pixel(cell, cx + px, cy + py);
+            }
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    pub fn fill_circle(&mut self, cell: &Cell, cx: isize, cy: isize, radius: isize) {
+        if radius < 0 {
+            return;
+        }
+        let y_range = (cy - radius).max(0)..=(cy + radius).min(self.height() - 1);
+        for y in y_range {
+            let dy = y - cy;
+            let half_width = (((radius * radius - dy * dy) as f32).sqrt()) as isize;
+            self.fill_rect(cell, cx - half_width, y, cx + half_width, y);
+        }
+    }
+
+    /// Filled polygon using the even-odd rule, pixels are filled if their center is inside.
+    pub fn fill_polygon(&mut self, cell: &Cell, points: &[(isize, isize)]) {
+        let points = points
+            .iter()
+            .map(|(x, y)| (*x as f32, *y as f32))
+            .collect::<Vec<_>>();
+        self.fill_polygon_f32(cell, &points);
+    }
+
+    fn fill_polygon_f32(&mut self, cell: &Cell, points: &[(f32, f32)]) {
+        if points.len() < 3 {
+            return;
+        }
+        let min_y = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+        let max_y = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+        let y_start = (min_y - 0.5).ceil().max(0.0) as isize;
+        let y_end = ((max_y - 0.5).ceil() as isize).min(self.height());
+
+        let mut crossings: Vec<f32> = Vec::new();
+        for y in y_start..y_end {
+            let sample_y = y as f32 + 0.5;
+            crossings.clear();
+            for (i, &(ax, ay)) in points.iter().enumerate() {
+                let (bx, by) = points[(i + 1) % points.len()];
+                // Half open, so vertices on the scanline aren't counted twice.
+                if (ay <= sample_y) != (by <= sample_y) {
+                    crossings.push(ax + (sample_y - ay) / (by - ay) * (bx - ax));
+                }
+            }
+            crossings.sort_by(f32::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let x_start = (span[0] - 0.5).ceil().max(0.0) as isize;
+                let x_end = ((span[1] - 0.5).ceil() as isize).min(self.width());
+                for x in x_start..x_end {
+                    self.pixel(cell, x, y);
+                }
+            }
+        }
+    }
+
+    /// Points of a rectangle, including both corners, clipped to the display.
     pub fn iter_rect(
         &self,
         mut x0: isize,
@@ -247,10 +928,11 @@ impl<'a> Drawer<'a> {
         mut x1: isize,
         mut y1: isize,
     ) -> impl Iterator<Item = (isize, isize)> {
-        x0 = isize::clamp(x0, 0, self.width());
-        x1 = isize::clamp(x1, 0, self.width());
-        y0 = isize::clamp(y0, 0, self.height());
-        y1 = isize::clamp(y1, 0, self.height());
+        let (max_x, max_y) = (self.width() - 1, self.height() - 1);
+        x0 = x0.max(0);
+        x1 = x1.min(max_x);
+        y0 = y0.max(0);
+        y1 = y1.min(max_y);
         (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (x, y)))
     }
 
@@ -300,3 +982,195 @@ impl<'a> Drawer<'a> {
         .filter(move |(x, y)| is_point_inside_triangle(*x, *y, x0, y0, x1, y1, x2, y2))
     }
 }
+
+#[test]
+fn layer_tests() {
+    let mut display = Display::init_with_size(&Cell::new_bg(Rgb(0, 0, 200)), 4, 1);
+    display.get_mut(1, 0).unwrap().c = 'x';
+
+    let overlay = display.layer_mut("overlay");
+    overlay
+        .display
+        .cells
+        .fill(Cell::new_bg(Rgb(200, 0, 0)).with_alpha(0.5));
+    overlay.drawer().text(2, 0, "a", None, Some(Rgb(0, 255, 0)));
+    display
+        .layer_mut("hud")
+        .drawer()
+        .text(3, 0, "b", None, None);
+
+    let composited = display.composite();
+    let cell = composited.get(0, 0).unwrap();
+    assert_eq!(cell.bg, Rgb(100, 0, 100));
+    // Glyph below shows through the semi-transparent overlay.
+    assert_eq!(composited.get(1, 0).unwrap().c, 'x');
+    assert_eq!(composited.get(2, 0).unwrap().fg, Rgb(0, 255, 0));
+    // Transparent HUD background keeps the overlay color.
+    let cell = composited.get(3, 0).unwrap();
+    assert_eq!((cell.c, cell.bg), ('b', Rgb(100, 0, 100)));
+
+    display.layer_mut("overlay").visible = false;
+    assert_eq!(display.composite().get(0, 0).unwrap().bg, Rgb(0, 0, 200));
+    assert!(display.remove_layer("overlay").is_some() && display.layer("overlay").is_none());
+}
+
+#[test]
+fn pack_tests() {
+    let background = Rgb(0, 0, 0);
+    let (red, green, blue) = (Rgb(255, 0, 0), Rgb(0, 255, 0), Rgb(0, 0, 255));
+    let options = AsciiOptions::default();
+
+    // Top sample is the foreground, the missing sample below an odd height is the background.
+    let mut framebuffer = Display::init_with_size(&Cell::new_bg(background), 2, 3);
+    *framebuffer.get_mut_unchecked(0, 0) = Cell::new_bg(red);
+    *framebuffer.get_mut_unchecked(0, 1) = Cell::new_bg(green);
+    *framebuffer.get_mut_unchecked(0, 2) = Cell::new_bg(blue);
+    let packed = framebuffer.pack(DisplayMode::HalfBlock, background, &options);
+    assert_eq!((packed.width(), packed.height()), (2, 2));
+    assert_eq!(*packed.get(0, 0).unwrap(), Cell::new(green, red, '▀'));
+    assert_eq!(*packed.get(0, 1).unwrap(), Cell::new(background, blue, '▀'));
+
+    // Top left & bottom right dots, colored with the average of their samples.
+    let mut framebuffer = Display::init_with_size(&Cell::new_bg(background), 2, 4);
+    *framebuffer.get_mut_unchecked(0, 0) = Cell::new_bg(red);
+    *framebuffer.get_mut_unchecked(1, 3) = Cell::new_bg(blue);
+    let packed = framebuffer.pack(DisplayMode::Braille, background, &options);
+    assert_eq!((packed.width(), packed.height()), (1, 1));
+    assert_eq!(
+        *packed.get(0, 0).unwrap(),
+        Cell::new(background, Rgb(127, 0, 127), '\u{2881}')
+    );
+
+    // Ramp by luminance, background samples are blank.
+    let white = Rgb(255, 255, 255);
+    let mut framebuffer = Display::init_with_size(&Cell::new_bg(background), 3, 1);
+    *framebuffer.get_mut_unchecked(0, 0) = Cell::new_bg(white);
+    *framebuffer.get_mut_unchecked(1, 0) = Cell::new_bg(Rgb(128, 128, 128));
+    let packed = framebuffer.pack(DisplayMode::Ascii, background, &options);
+    let chars = |packed: &Display| packed.cells.iter().map(|c| c.c).collect::<String>();
+    assert_eq!(chars(&packed), "@+ ");
+    assert_eq!(
+        *packed.get(0, 0).unwrap(),
+        Cell::new(background, white, '@')
+    );
+    let no_color = AsciiOptions {
+        color: false,
+        ..AsciiOptions::default()
+    };
+    let packed = framebuffer.pack(DisplayMode::Ascii, background, &no_color);
+    assert_eq!(
+        *packed.get(0, 0).unwrap(),
+        Cell {
+            c: '@',
+            ..Cell::default()
+        }
+    );
+
+    // Vertical & diagonal edges, border samples use the ramp.
+    let edges = AsciiOptions {
+        edges: true,
+        ..AsciiOptions::default()
+    };
+    let mut vertical = Display::init_with_size(&Cell::new_bg(background), 5, 5);
+    let mut diagonal = Display::init_with_size(&Cell::new_bg(background), 5, 5);
+    for (x, y) in (0..5).flat_map(|y| (0..5).map(move |x| (x, y))) {
+        if x >= 3 {
+            *vertical.get_mut_unchecked(x, y) = Cell::new_bg(white);
+        }
+        if x + y >= 5 {
+            *diagonal.get_mut_unchecked(x, y) = Cell::new_bg(white);
+        }
+    }
+    let packed = vertical.pack(DisplayMode::Ascii, background, &edges);
+    assert_eq!(&chars(&packed)[10..15], "  ||@");
+    let packed = diagonal.pack(DisplayMode::Ascii, background, &edges);
+    assert_eq!(packed.get(2, 2).unwrap().c, '/');
+    assert_eq!(packed.get(4, 4).unwrap().c, '@');
+}
+
+#[test]
+fn presenter_tests() {
+    let mut display = Display::init_with_size(&Cell::default(), 8, 4);
+    let mut presenter = Presenter::new(None);
+    let mut out = Vec::new();
+    presenter.present(&display, &mut out).unwrap();
+
+    display.get_mut(2, 1).unwrap().c = 'a';
+    display.get_mut(4, 1).unwrap().c = 'b';
+    let mut out = Vec::new();
+    let bytes = presenter.present(&display, &mut out).unwrap();
+    // Gap between the changed cells is rewritten instead of moving the cursor.
+    assert_eq!(String::from_utf8(out).unwrap(), "\x1B[2;3Ha b");
+    assert_eq!(bytes, 9);
+
+    let mut out = Vec::new();
+    assert_eq!(presenter.present(&display, &mut out).unwrap(), 0);
+}
+
+#[test]
+fn drawer_tests() {
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    let drawer = Drawer::new(&mut display);
+
+    // Every octant includes both ends & steps one pixel at a time.
+    for (x1, y1) in [
+        (13, 7),
+        (10, 11),
+        (3, 11),
+        (1, 8),
+        (1, 2),
+        (4, 0),
+        (11, 0),
+        (15, 3),
+    ] {
+        let points = drawer.iter_line(7, 5, x1, y1).collect::<Vec<_>>();
+        assert_eq!(points.first(), Some(&(7, 5)));
+        assert_eq!(points.last(), Some(&(x1, y1)));
+        assert_eq!(
+            points.len() as isize,
+            isize::max((x1 - 7).abs(), (y1 - 5).abs()) + 1
+        );
+    }
+    // Clipped to the display.
+    assert_eq!(drawer.iter_line(-100, 3, 100, 3).count(), 16);
+    assert_eq!(drawer.iter_line(-100, -3, 100, -3).count(), 0);
+
+    let count = |display: &Display| display.cells.iter().filter(|c| c.c == '#').count();
+    let cell = Cell::new(Rgb(0, 0, 0), Rgb(255, 255, 255), '#');
+
+    let mut drawer = Drawer::new(&mut display);
+    drawer.fill_polygon(&cell, &[(2, 2), (6, 2), (6, 5), (2, 5)]);
+    assert_eq!(count(&display), 4 * 3);
+
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    Drawer::new(&mut display).rect(&cell, 2, 2, 6, 5);
+    assert_eq!(count(&display), 14);
+
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    Drawer::new(&mut display).circle(&cell, 8, 6, 3);
+    assert!(display.get(11, 6).unwrap().c == '#' && display.get(8, 3).unwrap().c == '#');
+    assert_eq!(display.get(8, 6).unwrap().c, ' ');
+
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    Drawer::new(&mut display).thick_line(&cell, 2, 6, 13, 6, 3.0);
+    assert_eq!(count(&display), 12 * 3);
+
+    // Half covered pixels get half of the color.
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    let white = Rgb(255, 255, 255);
+    Drawer::new(&mut display).line_aa(white, 2.0, 4.5, 12.0, 4.5);
+    assert_eq!(display.get(6, 4).unwrap().bg, Rgb(128, 128, 128));
+    assert_eq!(display.get(6, 5).unwrap().bg, Rgb(128, 128, 128));
+
+    // Ends a quarter pixel past the end pixel centers cover 3/4 of them.
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    Drawer::new(&mut display).line_aa(white, 1.75, 4.0, 10.25, 4.0);
+    assert_eq!(display.get(2, 4).unwrap().bg, Rgb(191, 191, 191));
+    assert_eq!(display.get(10, 4).unwrap().bg, Rgb(191, 191, 191));
+    assert_eq!(display.get(6, 4).unwrap().bg, white);
+
+    // Half of a line just above the display covers the top row.
+    let mut display = Display::init_with_size(&Cell::default(), 16, 12);
+    Drawer::new(&mut display).line_aa(white, 2.0, -0.5, 12.0, -0.5);
+    assert_eq!(display.get(6, 0).unwrap().bg, Rgb(128, 128, 128));
+}
@@ -0,0 +1,190 @@
+//! Exporting a frame of terminal cells as text files.
+
+use std::{error::Error, io::Write, path::Path};
+
+use crate::{
+    color::{self, ColorOptions, Rgb, TermColor},
+    display::{AsciiOptions, Display, DisplayMode},
+    headless::Size,
+    renderer::{self, Renderer},
+};
+
+/// Render a frame & pack it into terminal cells, cell aspect is the cell width / height.
+pub fn render_cells(
+    renderer: &mut Renderer,
+    size: Size,
+    mode: DisplayMode,
+    ascii: &AsciiOptions,
+    cell_aspect: f32,
+) -> Result<Display, Box<dyn Error>> {
+    let (samples_x, samples_y) = mode.samples();
+    renderer.controller.camera.aspect = size.width as f32 * cell_aspect / size.height as f32;
+    let (framebuffer, _) = renderer.render(size.width * samples_x, size.height * samples_y)?;
+    Ok(framebuffer.pack(mode, renderer::BG, ascii))
+}
+
+/// Cell colors of every row, quantized to the color depth.
+fn quantized_colors(display: &Display, colors: &ColorOptions) -> (Vec<TermColor>, Vec<TermColor>) {
+    let cells = (0..display.height())
+        .flat_map(|y| (0..display.width()).map(move |x| (x, y)))
+        .map(|(x, y)| display.get_unchecked(x, y))
+        .collect::<Vec<_>>();
+    let quantize = |get: fn(&crate::display::Cell) -> Rgb| {
+        let rgb = cells
+            .iter()
+            .map(|c| {
+                let c = get(c);
+                [c.0, c.1, c.2]
+            })
+            .collect::<Vec<_>>();
+        color::quantize(&rgb, display.width(), display.height(), colors)
+    };
+    (quantize(|c| c.fg()), quantize(|c| c.bg()))
+}
+
+/// Write cells as text with escape codes, e.g. a ".ans" file that can be printed with cat.
+pub fn write_ansi(
+    mut out: impl Write,
+    display: &Display,
+    colors: Option<&ColorOptions>,
+) -> std::io::Result<()> {
+    let quantized = colors.map(|colors| quantized_colors(display, colors));
+    for y in 0..display.height() {
+        let mut current: Option<(TermColor, TermColor)> = None;
+        for x in 0..display.width() {
+            if let Some((fg, bg)) = &quantized {
+                let i = x + y * display.width();
+                if current != Some((fg[i], bg[i])) {
+                    fg[i].write_fg(&mut out)?;
+                    bg[i].write_bg(&mut out)?;
+                    current = Some((fg[i], bg[i]));
+                }
+            }
+            write!(out, "{}", display.get_unchecked(x, y).c())?;
+        }
+        // Reset before the line break, so the background doesn't fill the rest of the line.
+        if quantized.is_some() {
+            write!(out, "\x1B[0m")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write cells as a self contained HTML page, with a colored span for each run of equal colors.
+/// Colors are quantized like the terminal output, without colors only the characters are written.
+pub fn write_html(
+    mut out: impl Write,
+    display: &Display,
+    colors: Option<&ColorOptions>,
+) -> std::io::Result<()> {
+    let quantized = colors.map(|colors| quantized_colors(display, colors));
+    let hex = |c: TermColor| {
+        let [r, g, b] = match c {
+            TermColor::Rgb(r, g, b) => [r, g, b],
+            TermColor::Indexed(i) | TermColor::Ansi(i) => color::indexed_rgb(i),
+        };
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    };
+    let escape = |out: &mut dyn Write, c: char| match c {
+        '&' => write!(out, "&amp;"),
+        '<' => write!(out, "&lt;"),
+        '>' => write!(out, "&gt;"),
+        c => write!(out, "{}", c),
+    };
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(
+        out,
+        "<style>body {{ background: #000; color: #fff; }} pre {{ font-family: monospace; line-height: 1; }}</style>"
+    )?;
+    writeln!(out, "</head>\n<body>\n<pre>")?;
+    for y in 0..display.height() {
+        let row = y * display.width();
+        let Some((fg, bg)) = &quantized else {
+            for x in 0..display.width() {
+                escape(&mut out, display.get_unchecked(x, y).c())?;
+            }
+            writeln!(out)?;
+            continue;
+        };
+        let mut x = 0;
+        while x < display.width() {
+            let (run_fg, run_bg) = (fg[row + x], bg[row + x]);
+            write!(
+                out,
+                "<span style=\"color:{};background:{}\">",
+                hex(run_fg),
+                hex(run_bg)
+            )?;
+            while x < display.width() && fg[row + x] == run_fg && bg[row + x] == run_bg {
+                escape(&mut out, display.get_unchecked(x, y).c())?;
+                x += 1;
+            }
+            write!(out, "</span>")?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(out, "</pre>\n</body>\n</html>")
+}
+
+/// Write as HTML for ".html" & ".htm" paths, otherwise as text with escape codes.
+pub fn export(
+    path: &Path,
+    display: &Display,
+    colors: Option<&ColorOptions>,
+) -> Result<(), Box<dyn Error>> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(e) if e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm") => {
+            write_html(&mut out, display, colors)?
+        }
+        _ => write_ansi(&mut out, display, colors)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[test]
+fn export_tests() {
+    use crate::{color::ColorDepth, display::Cell};
+
+    let red = Rgb(255, 0, 0);
+    let white = Rgb(255, 255, 255);
+    let mut display = Display::init_with_size(&Cell::new(red, white, 'a'), 3, 2);
+    *display.get_mut(2, 0).unwrap() = Cell::new(Rgb(0, 0, 0), white, '<');
+
+    let mut out = Vec::new();
+    write_ansi(&mut out, &display, None).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "aa<\naaa\n");
+
+    let colors = ColorOptions {
+        depth: ColorDepth::TrueColor,
+        dither: color::Dither::None,
+    };
+    let mut out = Vec::new();
+    write_ansi(&mut out, &display, Some(&colors)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("\x1B[38;2;255;255;255m\x1B[48;2;255;0;0maa\x1B[38;2;255;255;255m\x1B[48;2;0;0;0m<\x1B[0m\n"));
+
+    let mut out = Vec::new();
+    write_html(&mut out, &display, Some(&colors)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(
+        "<span style=\"color:#ffffff;background:#ff0000\">aa</span><span style=\"color:#ffffff;background:#000000\">&lt;</span>\n"
+    ));
+
+    // Colors are quantized to the color depth, or left out.
+    let ansi16 = ColorOptions {
+        depth: ColorDepth::Ansi16,
+        dither: color::Dither::None,
+    };
+    let mut out = Vec::new();
+    write_html(&mut out, &display, Some(&ansi16)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<span style=\"color:#ffffff;background:#ff0000\">aaa</span>\n"));
+    let mut out = Vec::new();
+    write_html(&mut out, &display, None).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<pre>\naa&lt;\naaa\n</pre>") && !out.contains("<span"));
+}
@@ -0,0 +1,143 @@
+//! Rendering to images without a terminal.
+
+use std::{error::Error, path::Path, str::FromStr};
+
+use crate::{
+    camera::{CameraOrbitController, PerspectiveCamera},
+    renderer::Renderer,
+};
+
+/// Image size in pixels, parsed from "WIDTHxHEIGHT".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid size \"{}\", expected WIDTHxHEIGHT", s);
+        let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let size = Size {
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height: height.trim().parse().map_err(|_| invalid())?,
+        };
+        if size.width == 0 || size.height == 0 {
+            return Err(invalid());
+        }
+        Ok(size)
+    }
+}
+
+/// Orbit camera placement around the origin, parsed from "YAW,PITCH,DISTANCE" with the angles in
+/// degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPlacement {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Default for CameraPlacement {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 100.0,
+        }
+    }
+}
+
+impl FromStr for CameraPlacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid camera \"{}\": {}", s, e))?;
+        let [yaw, pitch, distance] = values[..] else {
+            return Err(format!(
+                "Invalid camera \"{}\", expected YAW,PITCH,DISTANCE",
+                s
+            ));
+        };
+        Ok(Self {
+            yaw,
+            pitch,
+            distance,
+        })
+    }
+}
+
+impl CameraPlacement {
+    /// Reset the controller camera & orbit it into place.
+    pub fn apply(&self, controller: &mut CameraOrbitController<PerspectiveCamera>) {
+        let camera = &controller.camera;
+        *controller =
+            CameraOrbitController::new(PerspectiveCamera::new(camera.fov, camera.near, camera.far));
+        controller.set_distance(self.distance);
+        controller.grab_move(self.yaw.to_radians(), self.pitch.to_radians());
+    }
+}
+
+/// Render the current camera view at pixel resolution, pixels are square.
+pub fn render_image(
+    renderer: &mut Renderer,
+    size: Size,
+) -> Result<image::RgbaImage, Box<dyn Error>> {
+    renderer.controller.camera.aspect = size.width as f32 / size.height as f32;
+    let (framebuffer, _) = renderer.render(size.width, size.height)?;
+    Ok(framebuffer.to_rgba_image())
+}
+
+/// Save image with the format from the file extension, alpha is dropped for formats without it.
+pub fn save_image(image: image::RgbaImage, path: &Path) -> Result<(), Box<dyn Error>> {
+    let image = image::DynamicImage::ImageRgba8(image);
+    match image::ImageFormat::from_path(path)? {
+        image::ImageFormat::Jpeg | image::ImageFormat::Pnm => image.to_rgb8().save(path)?,
+        _ => image.save(path)?,
+    }
+    Ok(())
+}
+
+#[test]
+fn headless_tests() {
+    use crate::scene::Scene;
+
+    assert_eq!(
+        "1920x1080".parse(),
+        Ok(Size {
+            width: 1920,
+            height: 1080
+        })
+    );
+    assert!("1920".parse::<Size>().is_err());
+    assert_eq!(
+        "90,-30,50".parse(),
+        Ok(CameraPlacement {
+            yaw: 90.0,
+            pitch: -30.0,
+            distance: 50.0
+        })
+    );
+
+    // Quad covering the center of the image.
+    let white = image::Rgb([255, 255, 255]);
+    let mut renderer = Renderer::new(Scene::from_quads(&[(20.0, 0.0, white)]));
+    CameraPlacement::default().apply(&mut renderer.controller);
+    let image = render_image(
+        &mut renderer,
+        Size {
+            width: 32,
+            height: 24,
+        },
+    )
+    .unwrap();
+    assert_eq!(image.dimensions(), (32, 24));
+    assert_eq!(image.get_pixel(16, 12).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(0, 0).0[3], 0);
+}
@@ -6,7 +6,6 @@ use std::collections::HashMap;
 use std::{error::Error, io::Read};
 
 use serde::Deserialize;
-use termion::terminal_size_pixels;
 
 use crate::material::{Material, MaterialGenericColor, MaterialGenericTexture};
 use crate::math::vector3::Vec3;
@@ -299,7 +298,7 @@ impl JsonRoot {
 
 #[derive(Debug)]
 enum Chunk {
-    Json(JsonRoot),
+    Json(Box<JsonRoot>),
     Bin(Box<[u8]>),
 }
 
@@ -322,7 +321,9 @@ pub fn load_scene(file: impl Read) -> Result<Scene, Box<dyn Error>> {
         let chunk_data = reader.read_buf(chunk_length as usize)?;
 
         match &chunk_type {
-            b"JSON" => chunks.push(Chunk::Json(serde_json::from_slice(&chunk_data).unwrap())),
+            b"JSON" => chunks.push(Chunk::Json(Box::new(
+                serde_json::from_slice(&chunk_data).unwrap(),
+            ))),
             b"BIN\0" => chunks.push(Chunk::Bin(chunk_data.into_boxed_slice())),
             _ => {}
         }
@@ -0,0 +1,119 @@
+use std::{error::Error, io::Read};
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::{
+    material::MaterialGenericTexture, math::vector3::Vec3, mesh::Mesh, scene::Scene, uv::Uv,
+};
+
+#[derive(Debug)]
+pub struct HeightmapOptions {
+    /// Horizontal distance between samples.
+    pub spacing: f32,
+    /// Height of a white sample.
+    pub scale: f32,
+    /// Only use every n-th sample, for large images.
+    pub decimate: usize,
+    /// Texture draped over the whole terrain, defaults to the heightmap itself.
+    pub texture: Option<RgbaImage>,
+}
+
+impl Default for HeightmapOptions {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            scale: 32.0,
+            decimate: 1,
+            texture: None,
+        }
+    }
+}
+
+pub fn load_scene(mut file: impl Read, options: HeightmapOptions) -> Result<Scene, Box<dyn Error>> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    from_image(image::load_from_memory(&data)?, options)
+}
+
+/// Grid mesh centered on the origin, with Y being the height.
+pub fn from_image(image: DynamicImage, options: HeightmapOptions) -> Result<Scene, Box<dyn Error>> {
+    if options.decimate == 0 {
+        return Err("Heightmap decimation must be at least 1".into());
+    }
+    let heights = image.to_luma16();
+    if heights.width() < 2 || heights.height() < 2 {
+        return Err("Heightmap must be at least 2x2".into());
+    }
+
+    // Always include the last row & column so decimation doesn't crop the terrain.
+    let samples = |length: u32| {
+        let mut samples = (0..length).step_by(options.decimate).collect::<Vec<_>>();
+        if *samples.last().unwrap() != length - 1 {
+            samples.push(length - 1);
+        }
+        samples
+    };
+    let xs = samples(heights.width());
+    let zs = samples(heights.height());
+
+    let half_width = (heights.width() - 1) as f32 * options.spacing / 2.0;
+    let half_depth = (heights.height() - 1) as f32 * options.spacing / 2.0;
+
+    let mut position: Vec<Vec3> = Vec::with_capacity(xs.len() * zs.len());
+    let mut texcoord: Vec<Uv> = Vec::with_capacity(xs.len() * zs.len());
+    for z in zs.iter() {
+        for x in xs.iter() {
+            let height = heights.get_pixel(*x, *z).0[0] as f32 / u16::MAX as f32;
+            position.push(Vec3::new(
+                *x as f32 * options.spacing - half_width,
+                height * options.scale,
+                *z as f32 * options.spacing - half_depth,
+            ));
+            texcoord.push(Uv::new(
+                *x as f32 / (heights.width() - 1) as f32,
+                *z as f32 / (heights.height() - 1) as f32,
+            ));
+        }
+    }
+
+    let w = xs.len();
+    let indices = (0..zs.len() - 1)
+        .flat_map(|z| {
+            (0..w - 1).flat_map(move |x| {
+                let i = x + z * w;
+                [(i, i + w, i + 1), (i + 1, i + w, i + w + 1)]
+            })
+        })
+        .collect();
+
+    let texture = options.texture.unwrap_or_else(|| image.to_rgba8());
+
+    let mut scene = Scene::new();
+    scene
+        .meshes
+        .push(Mesh::new(0, position, Some(texcoord), indices));
+    scene
+        .materials
+        .push(Box::new(MaterialGenericTexture::new(texture)));
+    Ok(scene)
+}
+
+#[test]
+fn heightmap_tests() {
+    let image = DynamicImage::ImageLuma8(image::GrayImage::from_fn(5, 3, |x, _| {
+        image::Luma([if x == 4 { 255 } else { 0 }])
+    }));
+    let scene = from_image(
+        image,
+        HeightmapOptions {
+            decimate: 3,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mesh = &scene.meshes[0];
+    // Columns 0, 3, 4 & rows 0, 2.
+    assert_eq!(mesh.position.len(), 6);
+    assert_eq!(mesh.indices.len(), 4);
+    assert_eq!(mesh.position[2], Vec3::new(2.0, 32.0, -1.0));
+}
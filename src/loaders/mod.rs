@@ -1,2 +1,143 @@
 pub mod gltf;
+pub mod heightmap;
 pub mod obj;
+pub mod off;
+pub mod ply;
+pub mod vox;
+
+use std::{error::Error, path::Path};
+
+use heightmap::HeightmapOptions;
+
+use crate::scene::Scene;
+
+#[derive(Debug, Default)]
+pub struct LoadOptions {
+    pub heightmap: HeightmapOptions,
+}
+
+pub type LoadFn = fn(&[u8], LoadOptions) -> Result<Scene, Box<dyn Error>>;
+
+pub struct Loader {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    /// Check if file content is of this format.
+    pub sniff: fn(&[u8]) -> bool,
+    pub load: LoadFn,
+}
+
+/// Loaders are sniffed in order, so loaders with weak checks go last.
+pub static LOADERS: &[Loader] = &[
+    Loader {
+        name: "gltf",
+        extensions: &["glb"],
+        sniff: |data| data.starts_with(b"glTF"),
+        load: |data, _| gltf::load_scene(data),
+    },
+    Loader {
+        name: "ply",
+        extensions: &["ply"],
+        sniff: |data| data.starts_with(b"ply\n") || data.starts_with(b"ply\r\n"),
+        load: |data, _| Ok(Scene::from_mesh(ply::load_mesh(data)?)),
+    },
+    Loader {
+        name: "vox",
+        extensions: &["vox"],
+        sniff: |data| data.starts_with(b"VOX "),
+        load: |data, _| vox::load_scene(data),
+    },
+    Loader {
+        name: "heightmap",
+        extensions: &["png", "tif", "tiff"],
+        sniff: |data| image::guess_format(data).is_ok(),
+        load: |data, options| heightmap::load_scene(data, options.heightmap),
+    },
+    Loader {
+        name: "off",
+        extensions: &["off"],
+        sniff: |data| {
+            // Keyword can have a prefix, e.g. "COFF", "NOFF", "STCNOFF".
+            first_text_token(data).is_some_and(|t| {
+                t.strip_suffix(b"OFF")
+                    .is_some_and(|prefix| prefix.iter().all(|c| b"STCN".contains(c)))
+            })
+        },
+        load: |data, _| Ok(Scene::from_mesh(off::load_mesh(data)?)),
+    },
+    Loader {
+        name: "obj",
+        extensions: &["obj"],
+        sniff: |data| {
+            const KEYWORDS: &[&[u8]] = &[b"v", b"vt", b"vn", b"f", b"o", b"g", b"mtllib", b"s"];
+            first_text_token(data).is_some_and(|t| KEYWORDS.contains(&t))
+        },
+        load: |data, _| Ok(Scene::from_mesh(obj::load_mesh(data)?)),
+    },
+];
+
+/// First whitespace separated token that isn't inside a "#" comment.
+fn first_text_token(data: &[u8]) -> Option<&[u8]> {
+    data.split(|c| *c == b'\n')
+        .map(|line| line.split(|c| *c == b'#').next().unwrap())
+        .find_map(|line| {
+            line.split(|c| c.is_ascii_whitespace())
+                .find(|token| !token.is_empty())
+        })
+}
+
+pub fn loader_by_name(name: &str) -> Result<&'static Loader, Box<dyn Error>> {
+    LOADERS
+        .iter()
+        .find(|loader| loader.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!(
+                "Invalid format \"{}\", expected one of: {}",
+                name,
+                LOADERS
+                    .iter()
+                    .map(|l| l.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .into()
+        })
+}
+
+/// Picks loader by file content, falling back to the file extension.
+pub fn detect_loader(path: &Path, data: &[u8]) -> Option<&'static Loader> {
+    LOADERS
+        .iter()
+        .find(|loader| (loader.sniff)(data))
+        .or_else(|| {
+            let extension = path.extension()?.to_str()?.to_lowercase();
+            LOADERS
+                .iter()
+                .find(|loader| loader.extensions.contains(&extension.as_str()))
+        })
+}
+
+/// Load scene from file, format is detected if not given.
+pub fn load_scene(
+    path: &Path,
+    format: Option<&str>,
+    options: LoadOptions,
+) -> Result<Scene, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let loader = match format {
+        Some(format) => loader_by_name(format)?,
+        None => detect_loader(path, &data)
+            .ok_or_else(|| format!("Could not detect format of \"{}\"", path.display()))?,
+    };
+    (loader.load)(&data, options)
+}
+
+#[test]
+fn detect_tests() {
+    let detect = |path: &str, data: &[u8]| detect_loader(Path::new(path), data).map(|l| l.name);
+    assert_eq!(detect("model", b"glTF\x02\0\0\0"), Some("gltf"));
+    assert_eq!(detect("model.obj", b"ply\nformat ascii 1.0\n"), Some("ply"));
+    assert_eq!(detect("model", b"# comment\nCOFF\n"), Some("off"));
+    assert_eq!(detect("model", b"# comment\n\nv 0 0 0\n"), Some("obj"));
+    assert_eq!(detect("model.vox", b"\0\0\0\0"), Some("vox"));
+    assert_eq!(detect("model", b"\0\0\0\0"), None);
+}
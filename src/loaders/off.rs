@@ -0,0 +1,262 @@
+// http://www.geomview.org/docs/html/OFF.html
+
+use std::{error::Error, io::Read};
+
+use image::Rgb;
+
+use crate::{math::vector3::Vec3, mesh::Mesh, uv::Uv};
+
+/// Unscaled color, see `color_scale`.
+fn parse_color(values: &[&str]) -> Result<[f32; 3], Box<dyn Error>> {
+    if values.len() < 3 {
+        return Err("OFF color has less than 3 components".into());
+    }
+    Ok([values[0].parse()?, values[1].parse()?, values[2].parse()?])
+}
+
+/// OFF colors are either floats (0.0 - 1.0) or integers (0 - 255) for the whole file.
+/// Integers if any component is above 1 or no color has a decimal point.
+fn color_scale<'a>(mut colors: impl Iterator<Item = &'a [f32; 3]>, has_decimal_point: bool) -> f32 {
+    if !has_decimal_point || colors.any(|c| c.iter().any(|v| *v > 1.0)) {
+        1.0
+    } else {
+        255.0
+    }
+}
+
+/// Loads OFF, COFF, NOFF, CNOFF & STOFF files, including per face colors.
+/// Polygon faces are triangulated as triangle fans.
+pub fn load_mesh(mut file: impl Read) -> Result<Mesh, Box<dyn Error>> {
+    let mut str = String::new();
+    file.read_to_string(&mut str)?;
+
+    let mut lines = str
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty());
+
+    let mut header = lines.next().ok_or("OFF file is empty")?;
+    let keyword = header.split_whitespace().next().unwrap();
+    let Some(prefix) = keyword.strip_suffix("OFF") else {
+        return Err("File is not OFF file".into());
+    };
+    if prefix.contains('4') || prefix.contains('n') {
+        return Err(format!("Unsupported OFF variant \"{}\"", keyword).into());
+    }
+    let has_texcoord = prefix.contains("ST");
+    let has_color = prefix.contains('C');
+    let has_normal = prefix.contains('N');
+
+    // Counts may be on the same line as the keyword.
+    header = header[keyword.len()..].trim();
+    if header.is_empty() {
+        header = lines.next().ok_or("OFF file is missing counts")?;
+    }
+    let counts = header
+        .split_whitespace()
+        .map(|c| c.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let [vertex_count, face_count, ..] = counts[..] else {
+        return Err("OFF file is missing counts".into());
+    };
+
+    // Counts aren't trusted for reserving, a malformed header would abort the process.
+    let mut position: Vec<Vec3> = Vec::new();
+    let mut normal: Vec<Vec3> = Vec::new();
+    let mut color: Vec<[f32; 3]> = Vec::new();
+    let mut has_decimal_point = false;
+    let mut texcoord: Vec<Uv> = Vec::new();
+
+    for _ in 0..vertex_count {
+        let line = lines.next().ok_or("Unexpected end of OFF file")?;
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        // Alpha is optional & ignored.
+        let color_len = match values.len().saturating_sub(if has_normal { 6 } else { 3 }) {
+            _ if !has_color => 0,
+            n if n >= 4 + if has_texcoord { 2 } else { 0 } => 4,
+            _ => 3,
+        };
+        let expected_len =
+            3 + if has_normal { 3 } else { 0 } + color_len + if has_texcoord { 2 } else { 0 };
+        if values.len() < expected_len {
+            return Err(format!("OFF vertex \"{}\" is missing values", line).into());
+        }
+
+        let mut values = &values[..];
+        let vec3 = |values: &mut &[&str]| -> Result<Vec3, Box<dyn Error>> {
+            let v = Vec3::new(values[0].parse()?, values[1].parse()?, values[2].parse()?);
+            *values = &values[3..];
+            Ok(v)
+        };
+
+        position.push(vec3(&mut values)?);
+        if has_normal {
+            normal.push(vec3(&mut values)?);
+        }
+        if has_color {
+            has_decimal_point |= values[..color_len].iter().any(|v| v.contains('.'));
+            color.push(parse_color(&values[..color_len])?);
+            values = &values[color_len..];
+        }
+        if has_texcoord {
+            texcoord.push(Uv::new(values[0].parse()?, 1.0 - values[1].parse::<f32>()?));
+        }
+    }
+
+    let mut faces: Vec<(Vec<usize>, Option<[f32; 3]>)> = Vec::new();
+    for _ in 0..face_count {
+        let line = lines.next().ok_or("Unexpected end of OFF file")?;
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        let count: usize = values[0].parse()?;
+        if count >= values.len() {
+            return Err(format!("OFF face \"{}\" is missing indices", line).into());
+        }
+        let indices = values[1..=count]
+            .iter()
+            .map(|i| {
+                let i = i.parse::<usize>()?;
+                if i >= vertex_count {
+                    return Err(format!("OFF face index {} is out of bounds", i).into());
+                }
+                Ok(i)
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        // Colormap indices (single value) are not supported.
+        let face_color = match &values[count + 1..] {
+            c if c.len() >= 3 => {
+                has_decimal_point |= c[..3].iter().any(|v| v.contains('.'));
+                Some(parse_color(c)?)
+            }
+            _ => None,
+        };
+        faces.push((indices, face_color));
+    }
+
+    let scale = color_scale(
+        color
+            .iter()
+            .chain(faces.iter().filter_map(|f| f.1.as_ref())),
+        has_decimal_point,
+    );
+    let to_rgb = |c: [f32; 3]| Rgb(c.map(|v| (v * scale).clamp(0.0, 255.0) as u8));
+    let color = color.into_iter().map(to_rgb).collect::<Vec<_>>();
+
+    let fan = |face: &[usize]| {
+        (1..face.len().saturating_sub(1))
+            .map(|i| (face[0], face[i], face[i + 1]))
+            .collect::<Vec<_>>()
+    };
+
+    let mesh = if faces.iter().any(|(_, c)| c.is_some()) {
+        // Per face colors, every face gets its own vertices.
+        let mut face_position = Vec::new();
+        let mut face_normal = Vec::new();
+        let mut face_texcoord = Vec::new();
+        let mut face_color = Vec::new();
+        let mut indices = Vec::new();
+        for (face, c) in faces.iter() {
+            let start = face_position.len();
+            for &i in face {
+                face_position.push(position[i]);
+                if has_normal {
+                    face_normal.push(normal[i]);
+                }
+                if has_texcoord {
+                    face_texcoord.push(texcoord[i]);
+                }
+                face_color.push(
+                    c.map(to_rgb)
+                        .or_else(|| color.get(i).copied())
+                        .unwrap_or(Rgb([255, 255, 255])),
+                );
+            }
+            indices.extend(fan(&(start..face_position.len()).collect::<Vec<_>>()));
+        }
+        let mesh = Mesh::new(
+            0,
+            face_position,
+            has_texcoord.then_some(face_texcoord),
+            indices,
+        )
+        .with_color(face_color);
+        if has_normal {
+            mesh.with_normal(face_normal)
+        } else {
+            mesh
+        }
+    } else {
+        let indices = faces.iter().flat_map(|(face, _)| fan(face)).collect();
+        let mut mesh = Mesh::new(0, position, has_texcoord.then_some(texcoord), indices);
+        if has_color {
+            mesh = mesh.with_color(color);
+        }
+        if has_normal {
+            mesh = mesh.with_normal(normal);
+        }
+        mesh
+    };
+
+    Ok(mesh)
+}
+
+#[test]
+fn off_tests() {
+    let coff = b"COFF
+# Quad with vertex colors
+4 1 0
+0 0 0 1.0 0.0 0.0 1.0
+1 0 0 0.0 1.0 0.0 1.0
+1 1 0 0.0 0.0 1.0 1.0
+0 1 0 1.0 1.0 1.0 1.0
+4 0 1 2 3
+";
+    let mesh = load_mesh(&coff[..]).unwrap();
+    assert_eq!(mesh.indices, vec![(0, 1, 2), (0, 2, 3)]);
+    assert_eq!(mesh.vertex_color(2), Rgb([0, 0, 255]));
+
+    let face_colors = b"OFF 4 2 0
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+3 0 1 2 255 0 0
+3 0 2 3 0 255 0
+";
+    let mesh = load_mesh(&face_colors[..]).unwrap();
+    assert_eq!(mesh.position.len(), 6);
+    assert_eq!(mesh.indices, vec![(0, 1, 2), (3, 4, 5)]);
+    assert_eq!(mesh.vertex_color(4), Rgb([0, 255, 0]));
+
+    // Float colors without a decimal point.
+    let float_colors = b"COFF 3 1 0
+0 0 0 1 0 0 1
+1 0 0 0 1e0 0 1
+1 1 0 0 0 0.5 1
+3 0 1 2
+";
+    let mesh = load_mesh(&float_colors[..]).unwrap();
+    assert_eq!(mesh.vertex_color(0), Rgb([255, 0, 0]));
+    assert_eq!(mesh.vertex_color(1), Rgb([0, 255, 0]));
+    assert_eq!(mesh.vertex_color(2), Rgb([0, 0, 127]));
+
+    // Integer colors starting with black.
+    let integer_colors = b"COFF 3 1 0
+0 0 0 0 0 0 255
+1 0 0 200 100 50 255
+1 1 0 1 1 1 255
+3 0 1 2
+";
+    let mesh = load_mesh(&integer_colors[..]).unwrap();
+    assert_eq!(mesh.vertex_color(0), Rgb([0, 0, 0]));
+    assert_eq!(mesh.vertex_color(1), Rgb([200, 100, 50]));
+    assert_eq!(mesh.vertex_color(2), Rgb([1, 1, 1]));
+
+    // Huge face vertex counts are errors.
+    let huge_face = b"OFF 3 1 0
+0 0 0
+1 0 0
+1 1 0
+18446744073709551615 0 1 2
+";
+    assert!(load_mesh(&huge_face[..]).is_err());
+}
@@ -0,0 +1,453 @@
+// http://paulbourke.net/dataformats/ply/
+
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+};
+
+use image::Rgb;
+
+use crate::{
+    math::vector3::Vec3,
+    mesh::Mesh,
+    reader::{Endianness, Reader},
+    uv::Uv,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PropertyType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl PropertyType {
+    fn parse(str: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(match str {
+            "char" | "int8" => PropertyType::Char,
+            "uchar" | "uint8" => PropertyType::UChar,
+            "short" | "int16" => PropertyType::Short,
+            "ushort" | "uint16" => PropertyType::UShort,
+            "int" | "int32" => PropertyType::Int,
+            "uint" | "uint32" => PropertyType::UInt,
+            "float" | "float32" => PropertyType::Float,
+            "double" | "float64" => PropertyType::Double,
+            _ => return Err(format!("Invalid PLY property type \"{}\"", str).into()),
+        })
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, PropertyType::Float | PropertyType::Double)
+    }
+
+    fn read<const E: Endianness>(&self, reader: &mut Reader<E, impl Read>) -> std::io::Result<f64> {
+        Ok(match self {
+            PropertyType::Char => reader.read_prim::<i8>()? as f64,
+            PropertyType::UChar => reader.read_prim::<u8>()? as f64,
+            PropertyType::Short => reader.read_prim::<i16>()? as f64,
+            PropertyType::UShort => reader.read_prim::<u16>()? as f64,
+            PropertyType::Int => reader.read_prim::<i32>()? as f64,
+            PropertyType::UInt => reader.read_prim::<u32>()? as f64,
+            PropertyType::Float => reader.read_prim::<f32>()? as f64,
+            PropertyType::Double => reader.read_prim::<f64>()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum PropertyKind {
+    Scalar(PropertyType),
+    List(PropertyType, PropertyType),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        names
+            .iter()
+            .find_map(|name| self.properties.iter().position(|p| p.name == *name))
+    }
+}
+
+/// Values of every row of an element, rows are stored flat to avoid an allocation per row.
+#[derive(Debug, Default)]
+struct ElementData {
+    /// One value per property, list properties store their index in `lists`.
+    values: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+    stride: usize,
+}
+
+impl ElementData {
+    fn rows(&self) -> usize {
+        self.values.len().checked_div(self.stride).unwrap_or(0)
+    }
+
+    /// First item for list properties.
+    fn scalar(&self, element: &Element, row: usize, property: usize) -> f64 {
+        let value = self.values[row * self.stride + property];
+        match element.properties[property].kind {
+            PropertyKind::Scalar(_) => value,
+            PropertyKind::List(_, _) => self.lists[value as usize].first().copied().unwrap_or(0.0),
+        }
+    }
+
+    fn list(&self, element: &Element, row: usize, property: usize) -> Option<&[f64]> {
+        match element.properties[property].kind {
+            PropertyKind::Scalar(_) => None,
+            PropertyKind::List(_, _) => {
+                Some(&self.lists[self.values[row * self.stride + property] as usize])
+            }
+        }
+    }
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), Box<dyn Error>> {
+    let mut lines = header.lines();
+    if lines.next().map(|l| l.trim()) != Some("ply") {
+        return Err("File is not PLY file".into());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for line in lines {
+        match &line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["format", f, _version] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("Invalid PLY format \"{}\"", f).into()),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or("PLY property defined before element")?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List(
+                        PropertyType::parse(count_type)?,
+                        PropertyType::parse(item_type)?,
+                    ),
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or("PLY property defined before element")?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(PropertyType::parse(ty)?),
+                }),
+            // "comment", "obj_info", etc.
+            _ => {}
+        }
+    }
+
+    Ok((format.ok_or("PLY file is missing format")?, elements))
+}
+
+/// Reads every element row, `next` reads a single value of the given type.
+/// Every value takes at least one byte, so counts larger than the body are rejected before
+/// anything is allocated for them.
+fn read_elements(
+    elements: &[Element],
+    body_len: usize,
+    mut next: impl FnMut(PropertyType) -> Result<f64, Box<dyn Error>>,
+) -> Result<Vec<ElementData>, Box<dyn Error>> {
+    let mut result = Vec::new();
+    for element in elements {
+        if element.count > body_len {
+            return Err(format!(
+                "PLY element \"{}\" count {} exceeds file size",
+                element.name, element.count
+            )
+            .into());
+        }
+        let mut data = ElementData {
+            stride: element.properties.len(),
+            ..Default::default()
+        };
+        for _ in 0..element.count {
+            for property in element.properties.iter() {
+                let value = match property.kind {
+                    PropertyKind::Scalar(ty) => next(ty)?,
+                    PropertyKind::List(count_type, item_type) => {
+                        let count = next(count_type)?;
+                        if count < 0.0 || count > body_len as f64 {
+                            return Err(format!("Invalid PLY list length {}", count).into());
+                        }
+                        let mut list = Vec::new();
+                        for _ in 0..count as usize {
+                            list.push(next(item_type)?);
+                        }
+                        data.lists.push(list);
+                        (data.lists.len() - 1) as f64
+                    }
+                };
+                data.values.push(value);
+            }
+        }
+        result.push(data);
+    }
+    Ok(result)
+}
+
+fn read_ascii(body: &[u8], elements: &[Element]) -> Result<Vec<ElementData>, Box<dyn Error>> {
+    let body = std::str::from_utf8(body)?;
+    let mut tokens = body.split_whitespace();
+    read_elements(elements, body.len(), |_| {
+        Ok(tokens.next().ok_or("Unexpected end of PLY file")?.parse()?)
+    })
+}
+
+fn read_binary<const E: Endianness>(
+    mut reader: Reader<E, Cursor<&[u8]>>,
+    body_len: usize,
+    elements: &[Element],
+) -> Result<Vec<ElementData>, Box<dyn Error>> {
+    read_elements(elements, body_len, |ty| Ok(ty.read(&mut reader)?))
+}
+
+fn read_color(element: &Element, data: &ElementData, row: usize, index: usize) -> u8 {
+    let value = data.scalar(element, row, index);
+    match element.properties[index].kind {
+        PropertyKind::Scalar(ty) if ty.is_float() => (value * 255.0).clamp(0.0, 255.0) as u8,
+        _ => value.clamp(0.0, 255.0) as u8,
+    }
+}
+
+/// Loads ascii, binary little endian & binary big endian PLY files.
+/// Files without faces are loaded as point clouds.
+pub fn load_mesh(mut file: impl Read) -> Result<Mesh, Box<dyn Error>> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    // end_header must be a whole line, it can also appear inside comments.
+    let mut header_end = None;
+    let mut line_start = 0;
+    while line_start < data.len() {
+        let line_end = data[line_start..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(data.len(), |p| line_start + p);
+        if data[line_start..line_end].trim_ascii() == b"end_header" {
+            header_end = Some((line_start, (line_end + 1).min(data.len())));
+            break;
+        }
+        line_start = line_end + 1;
+    }
+    // Body starts after the line break following end_header.
+    let (header_end, body_start) = header_end.ok_or("PLY file is missing end_header")?;
+
+    let (format, elements) = parse_header(std::str::from_utf8(&data[..header_end])?)?;
+    let body = &data[body_start..];
+
+    let data = match format {
+        Format::Ascii => read_ascii(body, &elements)?,
+        Format::BinaryLittleEndian => {
+            read_binary(Reader::new_le(Cursor::new(body)), body.len(), &elements)?
+        }
+        Format::BinaryBigEndian => {
+            read_binary(Reader::new_be(Cursor::new(body)), body.len(), &elements)?
+        }
+    };
+
+    let mut position: Vec<Vec3> = Vec::new();
+    let mut normal: Option<Vec<Vec3>> = None;
+    let mut texcoord: Option<Vec<Uv>> = None;
+    let mut color: Option<Vec<Rgb<u8>>> = None;
+    let mut indices: Vec<(usize, usize, usize)> = Vec::new();
+
+    for (element, data) in elements.iter().zip(data.iter()) {
+        let rows = 0..data.rows();
+        let scalar = |row: usize, property: usize| data.scalar(element, row, property) as f32;
+        match element.name.as_str() {
+            "vertex" => {
+                let x = element.property(&["x"]).ok_or("PLY vertex is missing x")?;
+                let y = element.property(&["y"]).ok_or("PLY vertex is missing y")?;
+                let z = element.property(&["z"]).ok_or("PLY vertex is missing z")?;
+                position = rows
+                    .clone()
+                    .map(|row| Vec3::new(scalar(row, x), scalar(row, y), scalar(row, z)))
+                    .collect();
+
+                if let (Some(nx), Some(ny), Some(nz)) = (
+                    element.property(&["nx"]),
+                    element.property(&["ny"]),
+                    element.property(&["nz"]),
+                ) {
+                    normal = Some(
+                        rows.clone()
+                            .map(|row| Vec3::new(scalar(row, nx), scalar(row, ny), scalar(row, nz)))
+                            .collect(),
+                    );
+                }
+
+                if let (Some(u), Some(v)) = (
+                    element.property(&["s", "u", "texture_u", "texture_s"]),
+                    element.property(&["t", "v", "texture_v", "texture_t"]),
+                ) {
+                    // PLY texture coordinates have their origin at the bottom left.
+                    texcoord = Some(
+                        rows.clone()
+                            .map(|row| Uv::new(scalar(row, u), 1.0 - scalar(row, v)))
+                            .collect(),
+                    );
+                }
+
+                if let (Some(r), Some(g), Some(b)) = (
+                    element.property(&["red", "r", "diffuse_red"]),
+                    element.property(&["green", "g", "diffuse_green"]),
+                    element.property(&["blue", "b", "diffuse_blue"]),
+                ) {
+                    color = Some(
+                        rows.clone()
+                            .map(|row| {
+                                Rgb([
+                                    read_color(element, data, row, r),
+                                    read_color(element, data, row, g),
+                                    read_color(element, data, row, b),
+                                ])
+                            })
+                            .collect(),
+                    );
+                }
+            }
+            "face" => {
+                let vertex_indices = element
+                    .property(&["vertex_indices", "vertex_index"])
+                    .ok_or("PLY face is missing vertex_indices")?;
+                for row in rows {
+                    let Some(face) = data.list(element, row, vertex_indices) else {
+                        return Err("PLY face vertex_indices is not a list".into());
+                    };
+                    if let Some(i) = face.iter().find(|i| **i < 0.0 || i.fract() != 0.0) {
+                        return Err(format!("Invalid PLY face index {}", i).into());
+                    }
+                    // Triangle fan, faces are expected to be convex.
+                    for i in 1..face.len().saturating_sub(1) {
+                        indices.push((face[0] as usize, face[i] as usize, face[i + 1] as usize));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((i0, i1, i2)) = indices
+        .iter()
+        .find(|(i0, i1, i2)| *i0.max(i1).max(i2) >= position.len())
+    {
+        return Err(format!("PLY face ({}, {}, {}) is out of bounds", i0, i1, i2).into());
+    }
+
+    let mut mesh = Mesh::new(0, position, texcoord, indices);
+    if let Some(normal) = normal {
+        mesh = mesh.with_normal(normal);
+    }
+    if let Some(color) = color {
+        mesh = mesh.with_color(color);
+    }
+    Ok(mesh)
+}
+
+#[test]
+fn ply_tests() {
+    let ascii = b"ply
+format ascii 1.0
+comment test
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+    let mesh = load_mesh(&ascii[..]).unwrap();
+    assert_eq!(mesh.position.len(), 4);
+    assert_eq!(mesh.indices, vec![(0, 1, 2), (0, 2, 3)]);
+    assert_eq!(mesh.color.as_ref().unwrap()[1], Rgb([0, 255, 0]));
+
+    let mut binary = b"ply
+format binary_big_endian 1.0
+element vertex 2
+property double x
+property double y
+property double z
+end_header
+"
+    .to_vec();
+    [1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
+        .iter()
+        .for_each(|v| binary.extend_from_slice(&v.to_be_bytes()));
+    let mesh = load_mesh(&binary[..]).unwrap();
+    assert!(mesh.is_point_cloud());
+    assert_eq!(mesh.position[1], Vec3::new(4.0, 5.0, 6.0));
+
+    // Bogus counts are errors, end_header in a comment isn't the end of the header.
+    let bogus = b"ply
+format ascii 1.0
+comment end_header
+element vertex 4000000000
+property float x
+end_header
+0
+";
+    assert!(load_mesh(&bogus[..]).is_err());
+
+    let negative_index = b"ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+1 1 0
+3 -1 1 2
+";
+    assert!(load_mesh(&negative_index[..]).is_err());
+}
@@ -0,0 +1,529 @@
+// https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
+// https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox-extension.txt
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{Cursor, Read},
+};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    material::MaterialGenericTexture,
+    math::vector3::Vec3,
+    mesh::Mesh,
+    reader::{Reader, LITTLE_ENDIAN},
+    scene::Scene,
+    uv::Uv,
+};
+
+#[derive(Debug)]
+struct Model {
+    size: [i32; 3],
+    /// x, y, z, color index
+    voxels: Vec<[u8; 4]>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Transform {
+        child: i32,
+        rotation: [[i32; 3]; 3],
+        translation: [i32; 3],
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+type Dict = HashMap<String, String>;
+
+/// Lengths are stored as i32, negative ones are errors.
+fn read_length(reader: &mut Reader<LITTLE_ENDIAN, impl Read>) -> Result<usize, Box<dyn Error>> {
+    let length = reader.read_prim::<i32>()?;
+    usize::try_from(length).map_err(|_| format!("Invalid MagicaVoxel length {}", length).into())
+}
+
+fn read_string(reader: &mut Reader<LITTLE_ENDIAN, impl Read>) -> Result<String, Box<dyn Error>> {
+    let length = read_length(reader)?;
+    Ok(String::from_utf8(reader.read_buf(length)?)?)
+}
+
+fn read_dict(reader: &mut Reader<LITTLE_ENDIAN, impl Read>) -> Result<Dict, Box<dyn Error>> {
+    let count = reader.read_prim::<i32>()?;
+    (0..count)
+        .map(|_| Ok((read_string(reader)?, read_string(reader)?)))
+        .collect()
+}
+
+/// Rotation is stored as a packed byte, each row has a single +1 or -1.
+fn decode_rotation(r: u8) -> [[i32; 3]; 3] {
+    let index0 = (r & 0b11) as usize;
+    let index1 = ((r >> 2) & 0b11) as usize;
+    let index2 = 3usize.saturating_sub(index0 + index1).min(2);
+    let sign = |bit: u8| if (r >> bit) & 1 == 1 { -1 } else { 1 };
+    let mut rotation = [[0; 3]; 3];
+    rotation[0][index0] = sign(4);
+    rotation[1][index1] = sign(5);
+    rotation[2][index2] = sign(6);
+    rotation
+}
+
+fn default_palette() -> [Rgba<u8>; 256] {
+    // The default palette is a 6x6x6 color cube followed by red, green, blue & gray ramps.
+    const RAMP: [u8; 10] = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut palette = [Rgba([0, 0, 0, 0]); 256];
+    for (i, color) in palette.iter_mut().enumerate().skip(1) {
+        let i = i - 1;
+        *color = match i {
+            0..=214 => Rgba([
+                0xFF - 0x33 * (i / 36) as u8,
+                0xFF - 0x33 * ((i / 6) % 6) as u8,
+                0xFF - 0x33 * (i % 6) as u8,
+                0xFF,
+            ]),
+            215..=224 => Rgba([RAMP[i - 215], 0, 0, 0xFF]),
+            225..=234 => Rgba([0, RAMP[i - 225], 0, 0xFF]),
+            235..=244 => Rgba([0, 0, RAMP[i - 235], 0xFF]),
+            _ => Rgba([RAMP[i - 245], RAMP[i - 245], RAMP[i - 245], 0xFF]),
+        };
+    }
+    palette
+}
+
+/// Face of merged voxels, with the normal being edge_u x edge_v.
+struct Quad {
+    corner: [i32; 3],
+    edge_u: [i32; 3],
+    edge_v: [i32; 3],
+    color: u8,
+}
+
+/// Dense voxel grid of a single model's palette indices, 0 is empty.
+struct VoxelGrid {
+    size: [i32; 3],
+    voxels: Vec<u8>,
+}
+
+impl VoxelGrid {
+    fn new(model: &Model) -> Result<Self, Box<dyn Error>> {
+        // Voxel coordinates are bytes, so models are at most 256 voxels per side.
+        if model.size.iter().any(|s| !(0..=256).contains(s)) {
+            return Err(format!("Invalid MagicaVoxel model size {:?}", model.size).into());
+        }
+        let size = model.size;
+        let mut grid = Self {
+            size,
+            voxels: vec![0; (size[0] * size[1] * size[2]) as usize],
+        };
+        for [x, y, z, c] in model.voxels.iter() {
+            let index = grid
+                .index([*x as i32, *y as i32, *z as i32])
+                .ok_or("MagicaVoxel voxel is outside of its model")?;
+            grid.voxels[index] = *c;
+        }
+        Ok(grid)
+    }
+
+    fn index(&self, p: [i32; 3]) -> Option<usize> {
+        if (0..3).any(|i| p[i] < 0 || p[i] >= self.size[i]) {
+            return None;
+        }
+        Some((p[0] + p[1] * self.size[0] + p[2] * self.size[0] * self.size[1]) as usize)
+    }
+
+    fn get(&self, p: [i32; 3]) -> u8 {
+        self.index(p).map(|i| self.voxels[i]).unwrap_or(0)
+    }
+
+    /// Greedy meshing, only faces between a voxel & empty space are generated.
+    fn greedy_mesh(&self) -> Vec<Quad> {
+        let mut quads = Vec::new();
+
+        for d in 0..3 {
+            let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+            let (w, h) = (self.size[u] as usize, self.size[v] as usize);
+            let mut mask: Vec<u8> = vec![0; w * h];
+
+            for sign in [1, -1] {
+                for slice in 0..self.size[d] {
+                    mask.iter_mut().enumerate().for_each(|(i, m)| {
+                        let mut p = [0; 3];
+                        p[d] = slice;
+                        p[u] = (i % w) as i32;
+                        p[v] = (i / w) as i32;
+                        let mut n = p;
+                        n[d] += sign;
+                        *m = if self.get(n) == 0 { self.get(p) } else { 0 };
+                    });
+
+                    for j in 0..h {
+                        let mut i = 0;
+                        while i < w {
+                            let color = mask[i + j * w];
+                            if color == 0 {
+                                i += 1;
+                                continue;
+                            }
+                            let mut quad_w = 1;
+                            while i + quad_w < w && mask[i + quad_w + j * w] == color {
+                                quad_w += 1;
+                            }
+                            let mut quad_h = 1;
+                            while j + quad_h < h
+                                && (i..i + quad_w).all(|x| mask[x + (j + quad_h) * w] == color)
+                            {
+                                quad_h += 1;
+                            }
+                            (j..j + quad_h)
+                                .for_each(|y| mask[i + y * w..i + quad_w + y * w].fill(0));
+
+                            let mut corner = [0; 3];
+                            corner[d] = slice + if sign > 0 { 1 } else { 0 };
+                            corner[u] = i as i32;
+                            corner[v] = j as i32;
+                            let mut edge_u = [0; 3];
+                            edge_u[u] = quad_w as i32;
+                            let mut edge_v = [0; 3];
+                            edge_v[v] = quad_h as i32;
+                            if sign < 0 {
+                                (edge_u, edge_v) = (edge_v, edge_u);
+                            }
+                            quads.push(Quad {
+                                corner,
+                                edge_u,
+                                edge_v,
+                                color,
+                            });
+
+                            i += quad_w;
+                        }
+                    }
+                }
+            }
+        }
+
+        quads
+    }
+}
+
+pub fn load_scene(file: impl Read) -> Result<Scene, Box<dyn Error>> {
+    let mut reader = Reader::new_le(file);
+
+    if &reader.read_prim::<[u8; 4]>()? != b"VOX " {
+        return Err("File is not MagicaVoxel file".into());
+    }
+    let _version = reader.read_prim::<i32>()?;
+
+    if &reader.read_prim::<[u8; 4]>()? != b"MAIN" {
+        return Err("MagicaVoxel file is missing MAIN chunk".into());
+    }
+    let main_content_length = read_length(&mut reader)?;
+    let main_children_length = read_length(&mut reader)?;
+    reader.read_buf(main_content_length)?;
+
+    let mut models: Vec<Model> = Vec::new();
+    let mut size: Option<[i32; 3]> = None;
+    let mut palette = default_palette();
+    let mut nodes: HashMap<i32, Node> = HashMap::new();
+
+    let mut offset = 0;
+    while offset < main_children_length {
+        let chunk_id = reader.read_prim::<[u8; 4]>()?;
+        let content_length = read_length(&mut reader)?;
+        let children_length = read_length(&mut reader)?;
+        let content = reader.read_buf(content_length)?;
+        reader.read_buf(children_length)?;
+        offset += 12 + content_length + children_length;
+
+        let mut chunk = Reader::new_le(Cursor::new(content));
+        match &chunk_id {
+            b"SIZE" => size = Some(chunk.read_prim::<[i32; 3]>()?),
+            b"XYZI" => {
+                let count = chunk.read_prim::<i32>()?;
+                let voxels = (0..count)
+                    .map(|_| chunk.read_prim::<[u8; 4]>())
+                    .collect::<Result<Vec<_>, _>>()?;
+                models.push(Model {
+                    size: size
+                        .take()
+                        .ok_or("MagicaVoxel XYZI chunk is missing SIZE chunk")?,
+                    voxels,
+                });
+            }
+            b"RGBA" => {
+                // Palette color i is color index i + 1.
+                for i in 0..255 {
+                    palette[i + 1] = Rgba(chunk.read_prim::<[u8; 4]>()?);
+                }
+            }
+            b"nTRN" => {
+                let id = chunk.read_prim::<i32>()?;
+                let _attributes = read_dict(&mut chunk)?;
+                let child = chunk.read_prim::<i32>()?;
+                let _reserved = chunk.read_prim::<i32>()?;
+                let _layer = chunk.read_prim::<i32>()?;
+                let frame_count = chunk.read_prim::<i32>()?;
+                // Only the first animation frame is used.
+                let frames = (0..frame_count)
+                    .map(|_| read_dict(&mut chunk))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let frame = frames.first();
+                let rotation = frame
+                    .and_then(|f| f.get("_r"))
+                    .map(|r| r.parse::<u8>())
+                    .transpose()?
+                    .map(decode_rotation)
+                    .unwrap_or([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+                let translation = match frame.and_then(|f| f.get("_t")) {
+                    Some(t) => match &t
+                        .split_whitespace()
+                        .map(|v| v.parse::<i32>())
+                        .collect::<Result<Vec<_>, _>>()?[..]
+                    {
+                        [x, y, z] => [*x, *y, *z],
+                        _ => {
+                            return Err(format!("Invalid MagicaVoxel translation \"{}\"", t).into())
+                        }
+                    },
+                    None => [0; 3],
+                };
+                nodes.insert(
+                    id,
+                    Node::Transform {
+                        child,
+                        rotation,
+                        translation,
+                    },
+                );
+            }
+            b"nGRP" => {
+                let id = chunk.read_prim::<i32>()?;
+                let _attributes = read_dict(&mut chunk)?;
+                let child_count = chunk.read_prim::<i32>()?;
+                let children = (0..child_count)
+                    .map(|_| chunk.read_prim::<i32>())
+                    .collect::<Result<Vec<_>, _>>()?;
+                nodes.insert(id, Node::Group { children });
+            }
+            b"nSHP" => {
+                let id = chunk.read_prim::<i32>()?;
+                let _attributes = read_dict(&mut chunk)?;
+                let model_count = chunk.read_prim::<i32>()?;
+                let models = (0..model_count)
+                    .map(|_| {
+                        let model = chunk.read_prim::<i32>()?;
+                        let _attributes = read_dict(&mut chunk)?;
+                        Ok(model)
+                    })
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                nodes.insert(id, Node::Shape { models });
+            }
+            // "PACK", "MATL", "LAYR", "rOBJ", "rCAM", "NOTE", "IMAP", etc.
+            _ => {}
+        }
+    }
+
+    /// Model index & its accumulated scene graph transform.
+    type Placement = (usize, [[i32; 3]; 3], [i32; 3]);
+    let mut placements: Vec<Placement> = Vec::new();
+
+    fn visit(
+        placements: &mut Vec<Placement>,
+        models: &[Model],
+        nodes: &HashMap<i32, Node>,
+        id: i32,
+        rotation: [[i32; 3]; 3],
+        translation: [i32; 3],
+        depth: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        if depth > 256 {
+            return Err("MagicaVoxel scene graph is too deep".into());
+        }
+        match nodes.get(&id) {
+            Some(Node::Transform {
+                child,
+                rotation: r,
+                translation: t,
+            }) => {
+                // Parent rotation * child transform.
+                let rotation_child = [0, 1, 2].map(|i| {
+                    [0, 1, 2].map(|j| (0..3).map(|k| rotation[i][k] * r[k][j]).sum::<i32>())
+                });
+                let translation_child = [0, 1, 2]
+                    .map(|i| (0..3).map(|k| rotation[i][k] * t[k]).sum::<i32>() + translation[i]);
+                visit(
+                    placements,
+                    models,
+                    nodes,
+                    *child,
+                    rotation_child,
+                    translation_child,
+                    depth + 1,
+                )
+            }
+            Some(Node::Group { children }) => children.iter().try_for_each(|child| {
+                visit(
+                    placements,
+                    models,
+                    nodes,
+                    *child,
+                    rotation,
+                    translation,
+                    depth + 1,
+                )
+            }),
+            Some(Node::Shape {
+                models: shape_models,
+            }) => shape_models.iter().try_for_each(|model| {
+                let model = usize::try_from(*model)
+                    .ok()
+                    .filter(|model| *model < models.len())
+                    .ok_or("MagicaVoxel shape references missing model")?;
+                placements.push((model, rotation, translation));
+                Ok(())
+            }),
+            None => Err(format!("MagicaVoxel scene graph is missing node {}", id).into()),
+        }
+    }
+
+    let identity = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+    if nodes.is_empty() {
+        placements.extend((0..models.len()).map(|model| (model, identity, [0; 3])));
+    } else {
+        visit(&mut placements, &models, &nodes, 0, identity, [0; 3], 0)?;
+    }
+
+    // Every model is meshed in its own grid once, the quads are transformed per placement.
+    let model_quads = models
+        .iter()
+        .map(|model| Ok(VoxelGrid::new(model)?.greedy_mesh()))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    let mut position: Vec<Vec3> = Vec::new();
+    let mut texcoord: Vec<Uv> = Vec::new();
+    let mut indices: Vec<(usize, usize, usize)> = Vec::new();
+
+    for (model, rotation, translation) in placements {
+        // Models are centered on their transform.
+        let half = models[model].size.map(|s| s / 2);
+        let rotate = |p: [i32; 3]| [0, 1, 2].map(|i| (0..3).map(|j| rotation[i][j] * p[j]).sum());
+        // MagicaVoxel is Z up, convert to Y up.
+        let transform = |p: [i32; 3]| {
+            let p: [i32; 3] = rotate([0, 1, 2].map(|i| p[i] - half[i]));
+            let p = [0, 1, 2].map(|i| (p[i] + translation[i]) as f32);
+            Vec3::new(p[0], p[2], -p[1])
+        };
+        // Mirroring transforms flip the winding, edge_u x edge_v must stay the normal.
+        let r = rotation;
+        let determinant = r[0][0] * (r[1][1] * r[2][2] - r[1][2] * r[2][1])
+            - r[0][1] * (r[1][0] * r[2][2] - r[1][2] * r[2][0])
+            + r[0][2] * (r[1][0] * r[2][1] - r[1][1] * r[2][0]);
+
+        for quad in model_quads[model].iter() {
+            let (edge_u, edge_v) = if determinant < 0 {
+                (quad.edge_v, quad.edge_u)
+            } else {
+                (quad.edge_u, quad.edge_v)
+            };
+            let add = |a: [i32; 3], b: [i32; 3]| [0, 1, 2].map(|i| a[i] + b[i]);
+            let start = position.len();
+            position.push(transform(quad.corner));
+            position.push(transform(add(quad.corner, edge_u)));
+            position.push(transform(add(add(quad.corner, edge_u), edge_v)));
+            position.push(transform(add(quad.corner, edge_v)));
+            // Sample the center of the color in the palette texture.
+            let uv = Uv::new((quad.color as f32 + 0.5) / 256.0, 0.5);
+            texcoord.extend([uv; 4]);
+            indices.push((start, start + 1, start + 2));
+            indices.push((start, start + 2, start + 3));
+        }
+    }
+
+    let mut palette_image = RgbaImage::new(256, 1);
+    palette
+        .iter()
+        .enumerate()
+        .for_each(|(i, color)| palette_image.put_pixel(i as u32, 0, *color));
+
+    let mut scene = Scene::new();
+    scene
+        .meshes
+        .push(Mesh::new(0, position, Some(texcoord), indices));
+    scene
+        .materials
+        .push(Box::new(MaterialGenericTexture::new(palette_image)));
+    Ok(scene)
+}
+
+#[test]
+fn vox_tests() {
+    fn chunk(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut data = id.to_vec();
+        data.extend((content.len() as i32).to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+        data.extend(content);
+        data
+    }
+
+    // 2x1x1 model of 2 voxels with the same color.
+    let mut size = Vec::new();
+    [2i32, 1, 1]
+        .iter()
+        .for_each(|v| size.extend(v.to_le_bytes()));
+    let mut xyzi = 2i32.to_le_bytes().to_vec();
+    xyzi.extend([0, 0, 0, 1, 1, 0, 0, 1]);
+    let model = [chunk(b"SIZE", &size), chunk(b"XYZI", &xyzi)].concat();
+
+    let file = |children: &[u8]| {
+        let mut file = b"VOX ".to_vec();
+        file.extend(150i32.to_le_bytes());
+        file.extend(b"MAIN");
+        file.extend(0i32.to_le_bytes());
+        file.extend((children.len() as i32).to_le_bytes());
+        file.extend(children);
+        file
+    };
+
+    let scene = load_scene(&file(&model)[..]).unwrap();
+    // Internal faces are removed & the remaining faces are merged into 6 quads.
+    assert_eq!(scene.meshes[0].indices.len(), 12);
+
+    // The model placed twice, far apart in the world.
+    let ints = |values: &[i32]| {
+        values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>()
+    };
+    let string = |s: &str| [ints(&[s.len() as i32]), s.as_bytes().to_vec()].concat();
+    let transform = |id: i32, child: i32, translation: &str| {
+        let frame = [ints(&[1]), string("_t"), string(translation)].concat();
+        let content = [ints(&[id, 0, child, -1, 0, 1]), frame].concat();
+        chunk(b"nTRN", &content)
+    };
+    let graph = [
+        transform(0, 1, "0 0 0"),
+        chunk(b"nGRP", &ints(&[1, 0, 2, 2, 4])),
+        transform(2, 3, "-1000 -1000 -1000"),
+        chunk(b"nSHP", &ints(&[3, 0, 1, 0, 0])),
+        transform(4, 3, "1000 1000 1000"),
+    ]
+    .concat();
+    let scene = load_scene(&file(&[model, graph].concat())[..]).unwrap();
+    let mesh = &scene.meshes[0];
+    assert_eq!(mesh.indices.len(), 24);
+    assert!(mesh.position.contains(&Vec3::new(1001.0, 1001.0, -1001.0)));
+
+    // Negative chunk lengths are errors.
+    let mut negative = b"VOX ".to_vec();
+    negative.extend(150i32.to_le_bytes());
+    negative.extend(b"MAIN");
+    negative.extend((-1i32).to_le_bytes());
+    negative.extend(0i32.to_le_bytes());
+    assert!(load_scene(&negative[..]).is_err());
+}
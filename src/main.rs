@@ -1,67 +1,268 @@
 mod camera;
+mod color;
 mod display;
+mod export;
+mod headless;
 mod loaders;
 mod material;
 mod math;
 mod mesh;
+mod output;
 mod raycast;
 mod reader;
+mod record;
 mod renderer;
 mod scene;
+mod terminal;
+mod turntable;
 mod uv;
 
-use std::{error::Error, fmt::Write, io::Write as _, path::PathBuf};
+use std::{
+    error::Error,
+    fmt::Write,
+    fs::File,
+    io::{BufWriter, Write as _},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-use clap::Parser;
-use display::Drawer;
-use material::MaterialGenericColor;
+use clap::{CommandFactory, Parser};
+use color::{ColorDepth, ColorOptions, Dither};
+use display::{AsciiOptions, DisplayMode, Presenter};
+use headless::{CameraPlacement, Size};
+use loaders::{heightmap::HeightmapOptions, LoadOptions};
+use output::{
+    cells::CellOutput, iterm::ItermOutput, kitty::KittyOutput, sixel::SixelOutput, Output,
+};
+use record::Recorder;
 use renderer::Renderer;
-use scene::Scene;
-use termion::{input::TermRead, raw::IntoRawMode};
+use terminal::{Event, Key, MouseButton, MouseKind, Terminal};
+use turntable::TurntableOptions;
 
-static CELL_ASPECT_RATIO: f32 = 9.0 / 20.0;
+/// Cell width / height, if it can't be detected.
+static FALLBACK_CELL_ASPECT_RATIO: f32 = 9.0 / 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+enum Backend {
+    /// Characters & colors of terminal cells, see --mode.
+    #[default]
+    Cells,
+    /// Sixel graphics at the terminal pixel resolution.
+    Sixel,
+    /// Kitty graphics protocol at the terminal pixel resolution, falls back to cells if the
+    /// terminal doesn't support it.
+    Kitty,
+    /// iTerm2 / WezTerm inline images at the terminal pixel resolution.
+    Iterm,
+}
 
 #[derive(Parser)]
 struct Cli {
     file: PathBuf,
+    /// File format, detected from file content & extension if not set.
+    #[arg(long)]
+    format: Option<String>,
+    /// How frames are output to the terminal.
+    #[arg(long, value_enum, default_value_t)]
+    backend: Backend,
+    /// How rendered pixels are displayed in terminal cells.
+    #[arg(long, value_enum, default_value_t)]
+    mode: DisplayMode,
+    /// Characters from darkest to brightest, for the ascii mode.
+    #[arg(long, default_value = " .:-=+*#%@")]
+    ascii_ramp: String,
+    /// Use edge direction characters in the ascii mode.
+    #[arg(long)]
+    ascii_edges: bool,
+    /// Terminal color depth, detected from COLORTERM & TERM if not set.
+    #[arg(long, value_enum)]
+    color_depth: Option<ColorDepth>,
+    /// Dithering used for the 256 & 16 color depths.
+    #[arg(long, value_enum, default_value_t)]
+    dither: Dither,
+    /// Maximum frames per second, frames are only rendered when something changed.
+    #[arg(long, default_value_t = 60.0)]
+    fps: f32,
+    /// Terminal cell width / height, detected from the terminal pixel size if not set.
+    #[arg(long)]
+    cell_aspect: Option<f32>,
+    /// Don't output any colors, only characters, for the ascii mode.
+    #[arg(long)]
+    no_color: bool,
+    /// Heightmap horizontal distance between samples.
+    #[arg(long, default_value_t = 1.0)]
+    heightmap_spacing: f32,
+    /// Heightmap height of a white sample.
+    #[arg(long, default_value_t = 32.0)]
+    heightmap_scale: f32,
+    /// Only use every n-th heightmap sample.
+    #[arg(long, default_value_t = 1)]
+    heightmap_decimate: usize,
+    /// Color texture draped over the heightmap.
+    #[arg(long)]
+    heightmap_texture: Option<PathBuf>,
+    /// Render a single frame to an image file instead of the terminal, format from the extension.
+    #[arg(long, conflicts_with_all = ["turntable", "export"])]
+    output: Option<PathBuf>,
+    /// Record the terminal output as an asciicast v2 file, for asciinema players.
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Render a single frame to a text file of terminal cells instead of using the terminal, as
+    /// HTML (.html) or text with escape codes (e.g. .ans). Uses --mode & the color options.
+    #[arg(long)]
+    export: Option<PathBuf>,
+    /// Size for --export in cells, as COLUMNSxROWS.
+    #[arg(long, default_value = "80x24")]
+    export_size: Size,
+    /// Render a 360° orbit around the scene instead of using the terminal, as a GIF (.gif), APNG
+    /// (.png/.apng) or a directory of numbered PNG files.
+    #[arg(long, conflicts_with = "export")]
+    turntable: Option<PathBuf>,
+    /// Frames of --turntable.
+    #[arg(long, default_value_t = 60)]
+    turntable_frames: usize,
+    /// Frame rate of --turntable.
+    #[arg(long, default_value_t = 30)]
+    turntable_fps: u16,
+    /// Image size for --output & --turntable, as WIDTHxHEIGHT.
+    #[arg(long, default_value = "1920x1080")]
+    size: Size,
+    /// Orbit camera for --output, --turntable & --export, as YAW,PITCH,DISTANCE with the angles in degrees.
+    #[arg(long, default_value = "0,0,100", allow_hyphen_values = true)]
+    camera: CameraPlacement,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
+    // Other modes only draw with colors, so they'd output nothing but blank cells.
+    if cli.no_color && cli.mode != DisplayMode::Ascii {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--no-color can only be used with --mode ascii",
+            )
+            .exit();
+    }
 
-    let scene = match cli.file.extension().and_then(|s| s.to_str()) {
-        Some("obj") => {
-            let mut scene = Scene::new();
-            let mesh = loaders::obj::load_mesh(std::fs::File::open(cli.file)?)?;
-            scene.meshes.push(mesh);
-            scene
-                .materials
-                .push(Box::new(MaterialGenericColor::new(image::Rgb([
-                    255, 255, 255,
-                ]))));
-            scene
-        }
-        Some("glb") => loaders::gltf::load_scene(std::fs::File::open(cli.file)?)?,
-        _ => panic!("Invalid file."),
+    let scene = loaders::load_scene(
+        &cli.file,
+        cli.format.as_deref(),
+        LoadOptions {
+            heightmap: HeightmapOptions {
+                spacing: cli.heightmap_spacing,
+                scale: cli.heightmap_scale,
+                decimate: cli.heightmap_decimate,
+                texture: match cli.heightmap_texture {
+                    Some(path) => Some(image::open(path)?.into_rgba8()),
+                    None => None,
+                },
+            },
+        },
+    )?;
+
+    if let Some(path) = &cli.output {
+        let mut renderer = Renderer::new(scene);
+        cli.camera.apply(&mut renderer.controller);
+        headless::save_image(headless::render_image(&mut renderer, cli.size)?, path)?;
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.turntable {
+        let mut renderer = Renderer::new(scene);
+        let options = TurntableOptions {
+            size: cli.size,
+            camera: cli.camera,
+            frames: cli.turntable_frames,
+            fps: cli.turntable_fps,
+        };
+        turntable::render_turntable(&mut renderer, options, path)?;
+        return Ok(());
+    }
+
+    let ascii_options = AsciiOptions {
+        ramp: cli.ascii_ramp.chars().collect(),
+        edges: cli.ascii_edges,
+        color: !cli.no_color,
     };
 
-    //let stdin = std::io::stdin();
-    // Initialize stdout for raw mode & mouse input.
-    let mut stdout = termion::input::MouseTerminal::from(std::io::stdout().lock().into_raw_mode()?);
-    let stdin = termion::async_stdin();
+    if let Some(path) = &cli.export {
+        let mut renderer = Renderer::new(scene);
+        cli.camera.apply(&mut renderer.controller);
+        let display = export::render_cells(
+            &mut renderer,
+            cli.export_size,
+            cli.mode,
+            &ascii_options,
+            cli.cell_aspect.unwrap_or(FALLBACK_CELL_ASPECT_RATIO),
+        )?;
+        // Files aren't tied to a terminal, so truecolor unless set.
+        let colors = ColorOptions {
+            depth: cli.color_depth.unwrap_or(ColorDepth::TrueColor),
+            dither: cli.dither,
+        };
+        export::export(path, &display, (!cli.no_color).then_some(&colors))?;
+        return Ok(());
+    }
+
+    // Raw mode & mouse input, restored when dropped.
+    let mut stdout = Terminal::new()?;
 
-    write!(
-        stdout,
-        "{}{}",
-        termion::screen::ToAlternateScreen,
-        termion::cursor::Hide,
-    )?;
     write!(
         stdout,
         "Press Q or ESC to quit. Leftclick & drag mouse to orbit. Leftclick + rightclick & drag mouse to pan. Scroll to zoom."
     )?;
     stdout.flush()?;
 
+    // The cell size query can only be answered before events are polled, so on resize only the
+    // terminal pixel size is used.
+    let queried_cell_size = match (cli.cell_aspect, terminal::cell_size_pixels()) {
+        (None, None) => terminal::query_cell_size(
+            terminal::NonBlockingStdin,
+            &mut stdout,
+            Duration::from_millis(500),
+        )?,
+        _ => None,
+    };
+    let detect_cell_aspect = || {
+        cli.cell_aspect
+            .or_else(|| {
+                terminal::cell_size_pixels()
+                    .or(queried_cell_size)
+                    .map(|(w, h)| w as f32 / h as f32)
+            })
+            .unwrap_or(FALLBACK_CELL_ASPECT_RATIO)
+    };
+    let mut cell_aspect = detect_cell_aspect();
+
+    let color_options = ColorOptions {
+        depth: cli.color_depth.unwrap_or_else(ColorDepth::detect),
+        dither: cli.dither,
+    };
+
+    let backend = match cli.backend {
+        Backend::Kitty
+            if !output::kitty::query_support(
+                terminal::NonBlockingStdin,
+                &mut stdout,
+                Duration::from_millis(500),
+            )? =>
+        {
+            Backend::Cells
+        }
+        backend => backend,
+    };
+    let mut output: Box<dyn Output> = match backend {
+        Backend::Cells => Box::new(CellOutput::new(
+            cli.mode,
+            ascii_options,
+            Presenter::new((!cli.no_color).then_some(color_options)),
+        )),
+        Backend::Sixel => Box::new(SixelOutput::new(cli.dither)),
+        Backend::Kitty => Box::new(KittyOutput::new()),
+        Backend::Iterm => Box::new(ItermOutput::new()),
+    };
+    let mut bytes_written = 0;
+
     let mut renderer = Renderer::new(scene);
     renderer.controller.set_distance(100.0);
 
@@ -69,87 +270,121 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut mouse_right: bool = false;
     let mut mouse_pos: (usize, usize) = (0, 0);
 
-    let mut events = stdin.events(); //.peekable();
-    'outer: loop {
-        let (width, height) = termion::terminal_size()?;
-        let (width, height) = (width as usize, height as usize);
-        renderer.controller.camera.aspect = (width as f32) * CELL_ASPECT_RATIO / (height as f32);
+    let (mut width, mut height) = terminal::size()?;
+    let mut recorder = match &cli.record {
+        Some(path) => Some(Recorder::new(
+            BufWriter::new(File::create(path)?),
+            width,
+            height,
+        )?),
+        None => None,
+    };
+
+    let frame_interval = Duration::from_secs_f32(1.0 / cli.fps.max(0.001));
+    let mut last_frame = Instant::now();
+    let mut redraw = true;
 
+    'outer: loop {
         let mut mouse_movement: (isize, isize) = (0, 0);
-        // FIXME: For some reason the iterator has an .unwrap() inside of it that is somehow
-        // panicing seemingly randomly @termion-4.0.2/src/event.rs:254:33?
-        // I'm pretty sure this is either a termion issue with decoding the mouse movement,
-        // or a kitty terminal issue with incorrectly encoding mouse movement?
-        // It happens so often it's extremely annoying.
-        for event in events.by_ref() {
-            let event = event?;
+        // Sleep until there's input, or until the next frame if something changed.
+        loop {
+            let timeout = if redraw {
+                frame_interval.saturating_sub(last_frame.elapsed())
+            } else {
+                Duration::from_secs(60)
+            };
+            let Some(event) = stdout.poll_event(timeout)? else {
+                if redraw {
+                    break;
+                }
+                continue;
+            };
             match event {
-                termion::event::Event::Key(termion::event::Key::Esc)
-                | termion::event::Event::Key(termion::event::Key::Char('q')) => break 'outer,
-                termion::event::Event::Mouse(termion::event::MouseEvent::Press(
-                    press_button,
+                Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => break 'outer,
+                Event::Resize(columns, rows) => {
+                    (width, height) = (columns, rows);
+                    cell_aspect = detect_cell_aspect();
+                    if let Some(recorder) = &mut recorder {
+                        recorder.resize(width, height)?;
+                    }
+                }
+                Event::Mouse {
+                    kind: MouseKind::ScrollUp,
+                    ..
+                } => renderer.controller.zoom_in(),
+                Event::Mouse {
+                    kind: MouseKind::ScrollDown,
+                    ..
+                } => renderer.controller.zoom_out(),
+                Event::Mouse {
+                    kind: MouseKind::Press(button),
                     x,
                     y,
-                )) => match press_button {
-                    termion::event::MouseButton::WheelUp => {
-                        renderer.controller.zoom_in();
-                    }
-                    termion::event::MouseButton::WheelDown => {
-                        renderer.controller.zoom_out();
+                } => {
+                    match button {
+                        MouseButton::Left => mouse_left = true,
+                        MouseButton::Right => mouse_right = true,
+                        _ => continue,
                     }
-                    _ => {
-                        match press_button {
-                            termion::event::MouseButton::Left => mouse_left = true,
-                            termion::event::MouseButton::Right => mouse_right = true,
-                            _ => {}
-                        }
-                        mouse_movement = (0, 0);
-                        mouse_pos = ((x as usize) - 1, (y as usize) - 1);
-                    }
-                },
-                termion::event::Event::Mouse(termion::event::MouseEvent::Release(x, y)) => {
+                    mouse_movement = (0, 0);
+                    mouse_pos = (x, y);
+                }
+                Event::Mouse {
+                    kind: MouseKind::Release(_),
+                    x,
+                    y,
+                } => {
                     mouse_left = false;
                     mouse_right = false;
                     mouse_movement = (
-                        (x as isize) - 1 - (mouse_pos.0 as isize),
-                        (y as isize) - 1 - (mouse_pos.1 as isize),
+                        x as isize - mouse_pos.0 as isize,
+                        y as isize - mouse_pos.1 as isize,
                     );
-                    mouse_pos = ((x as usize) - 1, (y as usize) - 1);
+                    mouse_pos = (x, y);
                 }
-                termion::event::Event::Mouse(termion::event::MouseEvent::Hold(x, y)) => {
+                Event::Mouse {
+                    kind: MouseKind::Drag(_),
+                    x,
+                    y,
+                } => {
                     mouse_movement = (
-                        (x as isize) - 1 - (mouse_pos.0 as isize),
-                        (y as isize) - 1 - (mouse_pos.1 as isize),
+                        x as isize - mouse_pos.0 as isize,
+                        y as isize - mouse_pos.1 as isize,
                     );
-                    mouse_pos = ((x as usize) - 1, (y as usize) - 1);
+                    mouse_pos = (x, y);
                     if mouse_movement.0 == 0 && mouse_movement.1 == 0 {
                         continue;
                     }
                 }
-                termion::event::Event::Key(termion::event::Key::Left) => {
+                Event::Key(Key::Left) => {
                     renderer.controller.roll(0.2);
                 }
-                termion::event::Event::Key(termion::event::Key::Right) => {
+                Event::Key(Key::Right) => {
                     renderer.controller.roll(-0.2);
                 }
-                _ => {}
+                _ => continue,
             }
+            // Events that don't change the camera, the size or the mouse state `continue` above.
+            redraw = true;
 
             if mouse_left {
                 if !mouse_right {
                     renderer.controller.grab_move(
                         (mouse_movement.0 as f32) / 10.0,
-                        (mouse_movement.1 as f32) / CELL_ASPECT_RATIO / 10.0,
+                        (mouse_movement.1 as f32) / cell_aspect / 10.0,
                     );
                 } else {
                     renderer.controller.pan_move(
                         (mouse_movement.0 as f32) / 100.0,
-                        (mouse_movement.1 as f32) / CELL_ASPECT_RATIO / 100.0,
+                        (mouse_movement.1 as f32) / cell_aspect / 100.0,
                     );
                 }
             }
         }
 
+        redraw = false;
+        last_frame = Instant::now();
+
         let mut dbg_text = String::new();
 
         writeln!(
@@ -160,16 +395,26 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         writeln!(dbg_text, "Controller: {:?}", renderer.controller)?;
 
-        let (mut display, render_info) = renderer.render(width, height)?;
+        // Pixel outputs don't cover the whole terminal, so the aspect is from the framebuffer.
+        let (framebuffer_width, framebuffer_height) = output.framebuffer_size(width, height)?;
+        renderer.controller.camera.aspect = framebuffer_width as f32
+            * output.sample_aspect(cell_aspect)
+            / framebuffer_height as f32;
+        let (framebuffer, mut render_info) =
+            renderer.render(framebuffer_width, framebuffer_height)?;
+        // Bytes of the previous frame, as this frame isn't presented yet.
+        render_info.bytes_written = bytes_written;
         writeln!(dbg_text, "Render info: {:?}", render_info)?;
 
-        let mut drawer = Drawer::new(&mut display);
-        drawer.text(0, 0, &dbg_text, None, None);
-
-        display.display(&mut stdout)?;
+        // Presented to a buffer first, so the same bytes can be recorded.
+        let mut frame: Vec<u8> = Vec::new();
+        bytes_written = output.present(framebuffer, &dbg_text, &mut frame)?;
+        stdout.write_all(&frame)?;
+        stdout.flush()?;
+        if let Some(recorder) = &mut recorder {
+            recorder.output(&frame)?;
+        }
     }
 
-    println!("{}{}", termion::screen::ToMainScreen, termion::cursor::Show);
-
     Ok(())
 }
@@ -2,7 +2,7 @@
 
 use image::{Pixel, Rgb, RgbaImage};
 
-pub trait Material: std::fmt::Debug {
+pub trait Material: std::fmt::Debug + Send + Sync {
     fn sample(&self, u: f32, v: f32) -> Rgb<u8>;
 }
 
@@ -193,6 +193,8 @@ impl Mul for Matrix4x4 {
 
 #[test]
 fn matrix_tests() {
+    use crate::vec3;
+
     let m1 = matrix4x4!(1, 2, 3, 4; 5, 6, 7, 8; 9, 10, 11, 12; 13, 14, 15, 16);
     assert_eq!(Matrix4x4::identity_matrix() * m1, m1);
     let m2 = matrix4x4!(17, 18, 19, 20; 21, 22, 23, 24; 25, 26, 27, 28; 29, 30, 31, 32);
@@ -206,5 +208,6 @@ fn matrix_tests() {
         ),
     );
     let v1 = vec3!(2, 4, 8);
-    assert_eq!(v1 * m2, vec3!(347, 362, 377));
+    // Vector multiplication divides by w (2 * 20 + 4 * 24 + 8 * 28 + 32).
+    assert_eq!(v1 * m2, vec3!(347, 362, 377) / 392.0);
 }
@@ -2,6 +2,8 @@
 
 use std::error::Error;
 
+use image::Rgb;
+
 use crate::{
     math::vector3::Vec3,
     raycast::{Ray, RayIntersection},
@@ -17,6 +19,10 @@ pub struct Mesh {
     pub material_index: usize,
     pub position: Vec<Vec3>,
     pub texcoord: Vec<Uv>,
+    /// Per vertex color, multiplied with the material color.
+    pub color: Option<Vec<Rgb<u8>>>,
+    pub normal: Option<Vec<Vec3>>,
+    /// Empty indices renders the mesh as a point cloud.
     pub indices: Vec<(usize, usize, usize)>,
 }
 
@@ -32,10 +38,34 @@ impl Mesh {
             texcoord: texcoord
                 .unwrap_or_else(|| position.iter().map(|_| Uv::new(0.0, 0.0)).collect()),
             position,
+            color: None,
+            normal: None,
             indices,
         }
     }
 
+    pub fn with_color(mut self, color: Vec<Rgb<u8>>) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_normal(mut self, normal: Vec<Vec3>) -> Self {
+        self.normal = Some(normal);
+        self
+    }
+
+    /// Vertex color, white if mesh has no vertex colors.
+    pub fn vertex_color(&self, index: usize) -> Rgb<u8> {
+        self.color
+            .as_ref()
+            .map(|c| c[index])
+            .unwrap_or(Rgb([255, 255, 255]))
+    }
+
+    pub fn is_point_cloud(&self) -> bool {
+        self.indices.is_empty()
+    }
+
     pub fn intersect(&self, ray: &Ray) -> Option<RayIntersection> {
         self.indices
             .iter()
@@ -0,0 +1,69 @@
+use std::io::Write;
+
+use super::Output;
+use crate::{
+    display::{AsciiOptions, Display, DisplayMode, Layer, Presenter},
+    renderer,
+};
+
+/// Framebuffer packed into terminal cells.
+#[derive(Debug)]
+pub struct CellOutput {
+    pub mode: DisplayMode,
+    pub ascii: AsciiOptions,
+    presenter: Presenter,
+    /// Text overlay, only redrawn when the text or size changes.
+    hud: Option<(String, Layer)>,
+}
+
+impl CellOutput {
+    pub fn new(mode: DisplayMode, ascii: AsciiOptions, presenter: Presenter) -> Self {
+        Self {
+            mode,
+            ascii,
+            presenter,
+            hud: None,
+        }
+    }
+}
+
+impl Output for CellOutput {
+    fn framebuffer_size(&mut self, columns: usize, rows: usize) -> std::io::Result<(usize, usize)> {
+        let (samples_x, samples_y) = self.mode.samples();
+        Ok((columns * samples_x, rows * samples_y))
+    }
+
+    fn sample_aspect(&self, cell_aspect: f32) -> f32 {
+        let (samples_x, samples_y) = self.mode.samples();
+        cell_aspect * samples_y as f32 / samples_x as f32
+    }
+
+    fn present(
+        &mut self,
+        framebuffer: &Display,
+        text: &str,
+        stdout: &mut dyn Write,
+    ) -> std::io::Result<usize> {
+        let mut display = framebuffer.pack(self.mode, renderer::BG, &self.ascii);
+        let hud = match self.hud.take() {
+            Some((hud_text, hud))
+                if hud_text == text
+                    && hud.display.width() == display.width()
+                    && hud.display.height() == display.height() =>
+            {
+                hud
+            }
+            _ => {
+                let mut hud = Layer::new("hud", display.width(), display.height());
+                hud.drawer().text(0, 0, text, None, None);
+                hud
+            }
+        };
+        display.push_layer(hud);
+        let written = self.presenter.present(&display, stdout)?;
+        self.hud = display
+            .remove_layer("hud")
+            .map(|hud| (text.to_string(), hud));
+        Ok(written)
+    }
+}
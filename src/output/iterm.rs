@@ -0,0 +1,73 @@
+// https://iterm2.com/documentation-images.html
+
+use std::io::Write;
+
+use base64::Engine;
+
+use super::Output;
+use crate::{display::Display, terminal};
+
+/// Framebuffer output as PNG inline images (OSC 1337), supported by iTerm2 & WezTerm.
+#[derive(Debug, Default)]
+pub struct ItermOutput {}
+
+impl ItermOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Write PNG encoded image as inline image, displayed at its pixel size.
+pub fn write_image(mut out: impl Write, image: &image::RgbImage) -> std::io::Result<()> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(std::io::Error::other)?;
+    write!(
+        out,
+        "\x1B]1337;File=inline=1;size={};width={}px;height={}px;preserveAspectRatio=0;doNotMoveCursor=1:",
+        png.len(),
+        image.width(),
+        image.height(),
+    )?;
+    out.write_all(
+        base64::engine::general_purpose::STANDARD
+            .encode(&png)
+            .as_bytes(),
+    )?;
+    write!(out, "\x07")
+}
+
+impl Output for ItermOutput {
+    fn framebuffer_size(&mut self, columns: usize, rows: usize) -> std::io::Result<(usize, usize)> {
+        Ok(super::image_framebuffer_size(columns, rows, 1))
+    }
+
+    fn present(
+        &mut self,
+        framebuffer: &Display,
+        text: &str,
+        stdout: &mut dyn Write,
+    ) -> std::io::Result<usize> {
+        let mut out: Vec<u8> = Vec::new();
+        terminal::move_to(&mut out, 0, 0)?;
+        write_image(&mut out, &framebuffer.to_rgb_image())?;
+        super::write_text(&mut out, text, true)?;
+        stdout.write_all(&out)?;
+        stdout.flush()?;
+        Ok(out.len())
+    }
+}
+
+#[test]
+fn iterm_tests() {
+    let image = image::RgbImage::new(4, 2);
+    let mut out = Vec::new();
+    write_image(&mut out, &image).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("\x1B]1337;File=inline=1;size="));
+    assert!(
+        out.contains(";width=4px;height=2px;preserveAspectRatio=0;doNotMoveCursor=1:iVBORw0KGgo")
+    );
+    assert!(out.ends_with('\x07'));
+}
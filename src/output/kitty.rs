@@ -0,0 +1,122 @@
+// https://sw.kovidgoyal.net/kitty/graphics-protocol/
+
+use std::{
+    io::{Read, Write},
+    time::Duration,
+};
+
+use base64::Engine;
+
+use super::Output;
+use crate::{display::Display, terminal};
+
+/// Image & placement id, so every frame replaces the previous one.
+const IMAGE_ID: u32 = 1;
+/// Max base64 bytes per escape code.
+const CHUNK_SIZE: usize = 4096;
+
+/// Framebuffer output with the kitty graphics protocol at the terminal pixel resolution.
+#[derive(Debug, Default)]
+pub struct KittyOutput {
+    columns: usize,
+    rows: usize,
+}
+
+impl KittyOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Query if the terminal supports the kitty graphics protocol.
+pub fn query_support(
+    mut stdin: impl Read,
+    mut stdout: impl Write,
+    timeout: Duration,
+) -> std::io::Result<bool> {
+    let response = terminal::query(
+        &mut stdin,
+        &mut stdout,
+        "\x1B_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1B\\",
+        timeout,
+    )?;
+    Ok(response.contains("\x1B_Gi=31;OK"))
+}
+
+/// Transmit & display RGB image in chunks, replacing the previous frame.
+pub fn write_image(
+    mut out: impl Write,
+    image: &image::RgbImage,
+    columns: usize,
+    rows: usize,
+) -> std::io::Result<()> {
+    let data = base64::engine::general_purpose::STANDARD.encode(image.as_raw());
+    let chunks = data.as_bytes().chunks(CHUNK_SIZE).collect::<Vec<_>>();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            // Placed below text (z=-1) so text can be drawn over it, cursor isn't moved (C=1).
+            write!(
+                out,
+                "\x1B_Ga=T,f=24,s={},v={},i={},p={},c={},r={},z=-1,C=1,q=2,m={};",
+                image.width(),
+                image.height(),
+                IMAGE_ID,
+                IMAGE_ID,
+                columns,
+                rows,
+                more,
+            )?;
+        } else {
+            write!(out, "\x1B_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1B\\")?;
+    }
+    Ok(())
+}
+
+impl Output for KittyOutput {
+    fn framebuffer_size(&mut self, columns: usize, rows: usize) -> std::io::Result<(usize, usize)> {
+        self.columns = columns;
+        self.rows = rows;
+        Ok(super::terminal_size_pixels(columns, rows))
+    }
+
+    fn present(
+        &mut self,
+        framebuffer: &Display,
+        text: &str,
+        stdout: &mut dyn Write,
+    ) -> std::io::Result<usize> {
+        let mut out: Vec<u8> = Vec::new();
+        terminal::move_to(&mut out, 0, 0)?;
+        write_image(
+            &mut out,
+            &framebuffer.to_rgb_image(),
+            self.columns,
+            self.rows,
+        )?;
+        super::write_text(&mut out, text, true)?;
+        stdout.write_all(&out)?;
+        stdout.flush()?;
+        Ok(out.len())
+    }
+}
+
+#[test]
+fn kitty_tests() {
+    // 3 * 2048 bytes of RGB is 8192 base64 bytes, so exactly 2 chunks.
+    let image = image::RgbImage::new(2048, 1);
+    let mut out = Vec::new();
+    write_image(&mut out, &image, 80, 24).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("\x1B_Ga=T,f=24,s=2048,v=1,i=1,p=1,c=80,r=24,z=-1,C=1,q=2,m=1;AAAA"));
+    assert_eq!(out.matches("\x1B_G").count(), 2);
+    assert!(out.contains("\x1B\\\x1B_Gm=0;AAAA"));
+
+    let mut response = &b"\x1B_Gi=31;OK\x1B\\\x1B[?62;4c"[..];
+    assert!(query_support(&mut response, std::io::sink(), Duration::from_millis(10)).unwrap());
+    let mut response = &b"\x1B[?62;c"[..];
+    assert!(!query_support(&mut response, std::io::sink(), Duration::from_millis(10)).unwrap());
+}
@@ -0,0 +1,64 @@
+pub mod cells;
+pub mod iterm;
+pub mod kitty;
+pub mod sixel;
+
+use std::io::Write;
+
+use crate::{display::Display, terminal};
+
+/// Terminal cell size in pixels, if the terminal doesn't report its pixel size.
+pub const FALLBACK_CELL_SIZE: (usize, usize) = (9, 20);
+
+/// Presents rendered framebuffers to the terminal.
+pub trait Output {
+    /// Framebuffer size to render at for a terminal of columns x rows cells.
+    fn framebuffer_size(&mut self, columns: usize, rows: usize) -> std::io::Result<(usize, usize)>;
+
+    /// Width / height of a framebuffer sample as displayed, pixels are square by default.
+    fn sample_aspect(&self, _cell_aspect: f32) -> f32 {
+        1.0
+    }
+
+    /// Present framebuffer with text drawn over it, returns the amount of bytes written.
+    fn present(
+        &mut self,
+        framebuffer: &Display,
+        text: &str,
+        stdout: &mut dyn Write,
+    ) -> std::io::Result<usize>;
+}
+
+/// Terminal size in pixels, estimated from the cell count if the terminal doesn't report it.
+pub fn terminal_size_pixels(columns: usize, rows: usize) -> (usize, usize) {
+    match terminal::size_pixels() {
+        Ok((width, height)) if width > 0 && height > 0 => (width, height),
+        _ => (columns * FALLBACK_CELL_SIZE.0, rows * FALLBACK_CELL_SIZE.1),
+    }
+}
+
+/// Pixel size of an image drawn over the terminal, with the height a multiple of row_multiple &
+/// at least one multiple. The last row is left empty, as images touching the bottom scroll the
+/// terminal.
+pub fn image_framebuffer_size(columns: usize, rows: usize, row_multiple: usize) -> (usize, usize) {
+    let (width, height) = terminal_size_pixels(columns, rows);
+    let height = height - height / rows.max(1);
+    (
+        width.max(1),
+        (height - height % row_multiple).max(row_multiple),
+    )
+}
+
+/// Write text lines at the top left of the terminal, for pixel outputs that can't draw text.
+/// Clearing the rest of the line removes text from previous frames.
+pub fn write_text(mut out: impl Write, text: &str, clear_line: bool) -> std::io::Result<()> {
+    terminal::reset_colors(&mut out)?;
+    for (y, line) in text.lines().enumerate() {
+        terminal::move_to(&mut out, 0, y)?;
+        write!(out, "{}", line)?;
+        if clear_line {
+            terminal::clear_line(&mut out)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// https://vt100.net/docs/vt3xx-gp/chapter14.html
+
+use std::{collections::BTreeMap, io::Write};
+
+use super::Output;
+use crate::{
+    color::{self, ColorDepth, ColorOptions, Dither, TermColor},
+    display::Display,
+    terminal,
+};
+
+/// Framebuffer output as a sixel image at the terminal pixel resolution.
+#[derive(Debug)]
+pub struct SixelOutput {
+    pub dither: Dither,
+}
+
+impl SixelOutput {
+    pub fn new(dither: Dither) -> Self {
+        Self { dither }
+    }
+}
+
+/// Encode image as sixels, colors are quantized to the xterm 256 color palette.
+pub fn encode(mut out: impl Write, image: &image::RgbImage, dither: Dither) -> std::io::Result<()> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let indices = color::quantize(
+        &image.pixels().map(|p| p.0).collect::<Vec<_>>(),
+        width,
+        height,
+        &ColorOptions {
+            depth: ColorDepth::Ansi256,
+            dither,
+        },
+    )
+    .into_iter()
+    .map(|c| match c {
+        TermColor::Indexed(i) => i,
+        _ => unreachable!(),
+    })
+    .collect::<Vec<_>>();
+
+    // Pixel aspect ratio 1:1, background is left as is.
+    write!(out, "\x1BP0;1;0q\"1;1;{};{}", width, height)?;
+
+    let mut used = [false; 256];
+    indices.iter().for_each(|i| used[*i as usize] = true);
+    for (i, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let [r, g, b] = color::indexed_rgb(i as u8).map(|c| c as u32 * 100 / 255);
+        write!(out, "#{};2;{};{};{}", i, r, g, b)?;
+    }
+
+    // Each sixel is a column of 6 pixels.
+    for band in 0..height.div_ceil(6) {
+        let mut rows: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+        for dy in 0..6 {
+            let y = band * 6 + dy;
+            if y >= height {
+                break;
+            }
+            for x in 0..width {
+                let index = indices[x + y * width];
+                rows.entry(index).or_insert_with(|| vec![0; width])[x] |= 1 << dy;
+            }
+        }
+
+        for (i, (index, sixels)) in rows.iter().enumerate() {
+            if i > 0 {
+                // Return to start of band.
+                write!(out, "$")?;
+            }
+            write!(out, "#{}", index)?;
+            // Run length encoding.
+            let mut x = 0;
+            while x < sixels.len() {
+                let run = sixels[x..].iter().take_while(|s| **s == sixels[x]).count();
+                let c = (63 + sixels[x]) as char;
+                if run > 3 {
+                    write!(out, "!{}{}", run, c)?;
+                } else {
+                    (0..run).try_for_each(|_| write!(out, "{}", c))?;
+                }
+                x += run;
+            }
+        }
+        write!(out, "-")?;
+    }
+
+    write!(out, "\x1B\\")?;
+    Ok(())
+}
+
+impl Output for SixelOutput {
+    fn framebuffer_size(&mut self, columns: usize, rows: usize) -> std::io::Result<(usize, usize)> {
+        // Sixels are 6 pixels high.
+        Ok(super::image_framebuffer_size(columns, rows, 6))
+    }
+
+    fn present(
+        &mut self,
+        framebuffer: &Display,
+        text: &str,
+        stdout: &mut dyn Write,
+    ) -> std::io::Result<usize> {
+        let mut out: Vec<u8> = Vec::new();
+        terminal::move_to(&mut out, 0, 0)?;
+        encode(&mut out, &framebuffer.to_rgb_image(), self.dither)?;
+        super::write_text(&mut out, text, false)?;
+        stdout.write_all(&out)?;
+        stdout.flush()?;
+        Ok(out.len())
+    }
+}
+
+#[test]
+fn sixel_tests() {
+    let image = image::RgbImage::from_fn(8, 7, |_, y| {
+        if y == 0 {
+            image::Rgb([255, 0, 0])
+        } else {
+            image::Rgb([0, 0, 0])
+        }
+    });
+    let mut out = Vec::new();
+    encode(&mut out, &image, Dither::None).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    // Black (16) & red (196) in the first band, only black in the second band.
+    assert_eq!(
+        out,
+        "\x1BP0;1;0q\"1;1;8;7#16;2;0;0;0#196;2;100;0;0#16!8}$#196!8@-#16!8@-\x1B\\"
+    );
+}
@@ -0,0 +1,79 @@
+//! Recording terminal output as asciicast v2.
+//! https://docs.asciinema.org/manual/asciicast/v2/
+
+use std::{io::Write, time::Instant};
+
+/// Writes the header & output events of an asciicast v2 recording.
+pub struct Recorder<W: Write> {
+    out: W,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Writes the header, width & height are in cells.
+    pub fn new(mut out: W, width: usize, height: usize) -> std::io::Result<Self> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+            "env": {
+                "TERM": std::env::var("TERM").unwrap_or_default(),
+            },
+        });
+        writeln!(out, "{}", header)?;
+        out.flush()?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+        })
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> std::io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(self.out, "{}", serde_json::json!([time, code, data]))?;
+        self.out.flush()
+    }
+
+    /// Record bytes written to the terminal.
+    pub fn output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &String::from_utf8_lossy(data))
+    }
+
+    /// Record terminal resize, in cells.
+    pub fn resize(&mut self, width: usize, height: usize) -> std::io::Result<()> {
+        self.event("r", &format!("{}x{}", width, height))
+    }
+}
+
+#[test]
+fn record_tests() {
+    let mut out = Vec::new();
+    let mut recorder = Recorder::new(&mut out, 80, 24).unwrap();
+    recorder.output(b"\x1B[1;1Hhi \"x\"").unwrap();
+    recorder.output(b"").unwrap();
+    recorder.resize(100, 30).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    let header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(header["version"], 2);
+    assert_eq!(
+        (header["width"].as_u64(), header["height"].as_u64()),
+        (Some(80), Some(24))
+    );
+    let event: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+    assert!(event[0].as_f64().unwrap() >= 0.0);
+    assert_eq!(event[1], "o");
+    assert_eq!(event[2], "\x1B[1;1Hhi \"x\"");
+    let event: serde_json::Value = serde_json::from_str(lines[2]).unwrap();
+    assert_eq!((&event[1], &event[2]), (&"r".into(), &"100x30".into()));
+}
@@ -2,24 +2,193 @@
 
 use std::error::Error;
 
-use image::Pixel;
+use image::{Pixel, Rgb};
+use rayon::prelude::*;
 
 use crate::{
     camera::{Camera, CameraOrbitController, PerspectiveCamera},
+    color,
     display::{Cell, Display, Drawer},
+    material::Material,
     math::vector3::Vec3,
     mesh::triangle_normal,
     scene::Scene,
     uv::Uv,
 };
 
-static BG_COLOR: Cell = Cell::new_bg(termion::color::Rgb(0, 0, 0));
+pub const BG: color::Rgb = color::Rgb(0, 0, 0);
+/// Transparent, so images rendered without a terminal have a transparent background.
+static BG_COLOR: Cell = Cell::new_bg(BG).with_alpha(0.0);
+
+/// Multiply material color with vertex color.
+fn modulate(color: Rgb<u8>, vertex_color: Rgb<u8>) -> Rgb<u8> {
+    Rgb([0, 1, 2].map(|i| ((color.0[i] as u16 * vertex_color.0[i] as u16) / 255) as u8))
+}
+
+#[derive(Debug)]
+struct RenderTriangle {
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    t0: Uv,
+    t1: Uv,
+    t2: Uv,
+    c0: Rgb<u8>,
+    c1: Rgb<u8>,
+    c2: Rgb<u8>,
+    view_normal: Vec3,
+    material_index: usize,
+}
+
+/// Vertex after the vertex stage.
+#[derive(Debug, Clone, Copy)]
+struct ProcessedVertex {
+    view: Vec3,
+    /// Position after projection & perspective divide.
+    projected: Vec3,
+    in_frustum: bool,
+}
+
+#[derive(Debug)]
+struct RenderPoint {
+    v: Vec3,
+    t: Uv,
+    c: Rgb<u8>,
+    material_index: usize,
+}
+
+impl RenderTriangle {
+    /// Screen space bounding box clipped to the framebuffer, None if outside of it.
+    fn bounds(&self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+        let (v0, v1, v2) = (self.v0, self.v1, self.v2);
+        let min_x = v0.x.min(v1.x).min(v2.x).max(0.0) as usize;
+        let min_y = v0.y.min(v1.y).min(v2.y).max(0.0) as usize;
+        let max_x = (v0.x.max(v1.x).max(v2.x) as isize).min(width as isize - 1);
+        let max_y = (v0.y.max(v1.y).max(v2.y) as isize).min(height as isize - 1);
+        if max_x < 0 || max_y < 0 || min_x > max_x as usize || min_y > max_y as usize {
+            return None;
+        }
+        Some((min_x, min_y, max_x as usize, max_y as usize))
+    }
+}
+
+/// Pixels per tile side.
+const TILE_SIZE: usize = 32;
+
+/// Framebuffer region with its own color & depth buffers, so tiles can be rasterized in
+/// parallel.
+#[derive(Debug)]
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    color: Vec<Cell>,
+    /// Projected depth, larger is closer.
+    depth: Vec<f32>,
+    /// Primitives overlapping the tile, in draw order.
+    triangles: Vec<usize>,
+    points: Vec<usize>,
+}
+
+impl Tile {
+    fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            color: vec![BG_COLOR; width * height],
+            depth: vec![f32::NEG_INFINITY; width * height],
+            triangles: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+
+    /// Rasterize the binned primitives, which are in screen space.
+    fn rasterize(
+        &mut self,
+        triangles: &[RenderTriangle],
+        points: &[RenderPoint],
+        materials: &[Box<dyn Material>],
+    ) {
+        self.color.fill(BG_COLOR);
+        self.depth.fill(f32::NEG_INFINITY);
+
+        for rt in self.triangles.iter().map(|i| &triangles[*i]) {
+            let material = &materials[rt.material_index];
+            // Bounding box clipped to the tile.
+            let (v0, v1, v2) = (rt.v0, rt.v1, rt.v2);
+            let Some((min_x, min_y, max_x, max_y)) =
+                rt.bounds(self.x + self.width, self.y + self.height)
+            else {
+                continue;
+            };
+            let denom = (v1.y - v2.y) * (v0.x - v2.x) + (v2.x - v1.x) * (v0.y - v2.y);
+            for py in min_y.max(self.y)..=max_y {
+                for px in min_x.max(self.x)..=max_x {
+                    let a = ((v1.y - v2.y) * (px as f32 - v2.x)
+                        + (v2.x - v1.x) * (py as f32 - v2.y))
+                        / denom;
+                    let b = ((v2.y - v0.y) * (px as f32 - v2.x)
+                        + (v0.x - v2.x) * (py as f32 - v2.y))
+                        / denom;
+                    let c = 1.0 - a - b;
+
+                    if a >= 0.0 && b >= 0.0 && c >= 0.0 {
+                        // Projected depth is linear in screen space.
+                        let z = a * v0.z + b * v1.z + c * v2.z;
+                        let i = (px - self.x) + (py - self.y) * self.width;
+                        if z <= self.depth[i] {
+                            continue;
+                        }
+                        self.depth[i] = z;
+
+                        let uv = Uv::new(
+                            a * rt.t0.u + b * rt.t1.u + c * rt.t2.u,
+                            a * rt.t0.v + b * rt.t1.v + c * rt.t2.v,
+                        );
+
+                        let vertex_color = Rgb([0, 1, 2].map(|i| {
+                            // Rounded, as the barycentric weights don't always sum to exactly 1.
+                            (a * rt.c0.0[i] as f32 + b * rt.c1.0[i] as f32 + c * rt.c2.0[i] as f32)
+                                .round() as u8
+                        }));
+                        let color = modulate(material.sample(uv.u, uv.v), vertex_color);
+                        self.color[i] =
+                            Cell::new_bg(color::Rgb(color.0[0], color.0[1], color.0[2]));
+                    }
+                }
+            }
+        }
+
+        for rp in self.points.iter().map(|i| &points[*i]) {
+            let (px, py) = (rp.v.x as usize, rp.v.y as usize);
+            let i = (px - self.x) + (py - self.y) * self.width;
+            if rp.v.z <= self.depth[i] {
+                continue;
+            }
+            self.depth[i] = rp.v.z;
+            let material = &materials[rp.material_index];
+            let color = modulate(material.sample(rp.t.u, rp.t.v), rp.c);
+            self.color[i] = Cell::new_bg(color::Rgb(color.0[0], color.0[1], color.0[2]));
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Renderer {
     pub scene: Scene,
     pub controller: CameraOrbitController<PerspectiveCamera>,
     render_count: u64,
+    /// Buffers are kept between frames & only reallocated when the size changes.
+    framebuffer: Display,
+    /// Framebuffer split into tiles, in row order.
+    tiles: Vec<Tile>,
+    /// Processed vertices of every mesh, indexed like the mesh positions.
+    vertices: Vec<Vec<ProcessedVertex>>,
+    render_triangles: Vec<RenderTriangle>,
+    render_points: Vec<RenderPoint>,
 }
 
 #[derive(Debug)]
@@ -27,6 +196,8 @@ pub struct RenderInfo {
     pub dbg_text: String,
     pub render_time: std::time::Duration,
     pub render_count: u64,
+    /// Bytes written to the terminal, set after the frame is presented.
+    pub bytes_written: usize,
 }
 
 impl Renderer {
@@ -35,175 +206,217 @@ impl Renderer {
             scene,
             controller: CameraOrbitController::new(PerspectiveCamera::new(90.0, 0.1, 1000.0)),
             render_count: 0,
+            framebuffer: Display::init_with_size(&BG_COLOR, 0, 0),
+            tiles: Vec::new(),
+            vertices: Vec::new(),
+            render_triangles: Vec::new(),
+            render_points: Vec::new(),
         }
     }
 
-    pub fn render_inner(
-        &self,
-        drawer: &mut Drawer,
-        mut dbg_text: impl std::fmt::Write,
-    ) -> Result<(), Box<dyn Error>> {
+    /// Vertex stage, transforms the vertices of every mesh once per frame.
+    fn process_vertices(&mut self) {
         let camera_matrix = self.controller.camera.matrix_view();
-        let camera_frustum = self.controller.camera.frustum();
+        let camera_frustum = &self.controller.camera.frustum();
         let projection_matrix = self.controller.camera.matrix_projection();
 
-        writeln!(
-            dbg_text,
-            "Out buffer: {}x{}",
-            drawer.width(),
-            drawer.height(),
-        )?;
-
-        #[derive(Debug)]
-        struct RenderTriangle {
-            v0: Vec3,
-            v1: Vec3,
-            v2: Vec3,
-            t0: Uv,
-            t1: Uv,
-            t2: Uv,
-            view_normal: Vec3,
-            material_index: usize,
+        self.vertices.resize_with(self.scene.meshes.len(), Vec::new);
+        for (mesh, vertices) in self.scene.meshes.iter().zip(self.vertices.iter_mut()) {
+            vertices.clear();
+            vertices.extend(mesh.position.iter().map(|position| {
+                let view = *position * camera_matrix;
+                ProcessedVertex {
+                    view,
+                    projected: view * projection_matrix,
+                    in_frustum: camera_frustum.contains(&view),
+                }
+            }));
         }
+    }
 
-        let mut render_triangles: Vec<RenderTriangle> = self
-            .scene
-            .meshes
-            .iter()
-            .flat_map(|mesh| {
-                mesh.indices.iter().filter_map(|(i0, i1, i2)| {
-                    let mut v0 = mesh.position[*i0];
-                    let mut v1 = mesh.position[*i1];
-                    let mut v2 = mesh.position[*i2];
+    /// Primitive assembly, builds triangles & points from the processed vertices.
+    fn assemble_primitives(&mut self) {
+        self.render_triangles.clear();
+        self.render_points.clear();
 
-                    let cam_v0 = v0 * camera_matrix;
-                    let cam_v1 = v1 * camera_matrix;
-                    let cam_v2 = v2 * camera_matrix;
+        for (mesh, vertices) in self.scene.meshes.iter().zip(self.vertices.iter()) {
+            self.render_triangles
+                .extend(mesh.indices.iter().filter_map(|&(i0, i1, i2)| {
+                    let (v0, v1, v2) = (vertices[i0], vertices[i1], vertices[i2]);
 
                     // HACK: *Bad* frustum culling
-                    if !camera_frustum.contains(&cam_v0)
-                        || !camera_frustum.contains(&cam_v1)
-                        || !camera_frustum.contains(&cam_v2)
-                    {
+                    if !v0.in_frustum || !v1.in_frustum || !v2.in_frustum {
                         return None;
                     }
 
-                    let cam_normal = triangle_normal(&cam_v0, &cam_v1, &cam_v2);
+                    let cam_normal = triangle_normal(&v0.view, &v1.view, &v2.view);
 
                     // Backside culling
                     if cam_normal.z > 0.0 {
                         return None;
                     }
 
-                    let proj_v0 = cam_v0 * projection_matrix;
-                    let proj_v1 = cam_v1 * projection_matrix;
-                    let proj_v2 = cam_v2 * projection_matrix;
-
                     Some(RenderTriangle {
-                        v0: proj_v0,
-                        v1: proj_v1,
-                        v2: proj_v2,
-                        t0: mesh.texcoord[*i0],
-                        t1: mesh.texcoord[*i1],
-                        t2: mesh.texcoord[*i2],
+                        v0: v0.projected,
+                        v1: v1.projected,
+                        v2: v2.projected,
+                        t0: mesh.texcoord[i0],
+                        t1: mesh.texcoord[i1],
+                        t2: mesh.texcoord[i2],
+                        c0: mesh.vertex_color(i0),
+                        c1: mesh.vertex_color(i1),
+                        c2: mesh.vertex_color(i2),
                         view_normal: cam_normal,
                         material_index: mesh.material_index,
                     })
-                })
-            })
-            .collect::<Vec<_>>();
-
-        // HACK: Temporary triangle depth 'check'
-        render_triangles
-            .sort_by(|a, b| (a.v0.z + a.v1.z + a.v2.z).total_cmp(&(b.v0.z + b.v1.z + b.v2.z)));
-
-        let screenspace_mul_vec = Vec3::new(
-            drawer.width() as f32 / 2.0,
-            drawer.height() as f32 / 2.0,
-            1.0,
-        );
-
-        render_triangles.into_iter().for_each(|rt| {
-            #[rustfmt::skip]
-            let screen_v0 = (rt.v0 + Vec3::new(1.0, 1.0, 0.0)) * screenspace_mul_vec;
-            let screen_v1 = (rt.v1 + Vec3::new(1.0, 1.0, 0.0)) * screenspace_mul_vec;
-            let screen_v2 = (rt.v2 + Vec3::new(1.0, 1.0, 0.0)) * screenspace_mul_vec;
-
-            let material = &self.scene.materials[rt.material_index];
-            //let mut color = material.sample(0.0, 0.0);
-
-            //color.channels_mut().iter_mut().for_each(|c| {
-            //    *c = ((*c as f32) * -rt.view_normal.z) as u8;
-            //});
-
-            //let cell = Cell::new_bg(termion::color::Rgb(color.0[0], color.0[1], color.0[2]));
-
-            drawer
-                .iter_rect(
-                    (screen_v0.x as isize)
-                        .min(screen_v1.x as isize)
-                        .min(screen_v2.x as isize),
-                    (screen_v0.y as isize)
-                        .min(screen_v1.y as isize)
-                        .min(screen_v2.y as isize),
-                    (screen_v0.x as isize)
-                        .max(screen_v1.x as isize)
-                        .max(screen_v2.x as isize),
-                    (screen_v0.y as isize)
-                        .max(screen_v1.y as isize)
-                        .max(screen_v2.y as isize),
-                )
-                .for_each(|(px, py)| {
-                    let (v0, v1, v2) = (screen_v0, screen_v1, screen_v2);
-                    let denom = (v1.y - v2.y) * (v0.x - v2.x) + (v2.x - v1.x) * (v0.y - v2.y);
-                    let a = ((v1.y - v2.y) * (px as f32 - v2.x)
-                        + (v2.x - v1.x) * (py as f32 - v2.y))
-                        / denom;
-                    let b = ((v2.y - v0.y) * (px as f32 - v2.x)
-                        + (v0.x - v2.x) * (py as f32 - v2.y))
-                        / denom;
-                    let c = 1.0 - a - b;
+                }));
+
+            if mesh.is_point_cloud() {
+                self.render_points.extend(
+                    vertices
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, v)| v.in_frustum)
+                        .map(|(i, v)| RenderPoint {
+                            v: v.projected,
+                            t: mesh.texcoord[i],
+                            c: mesh.vertex_color(i),
+                            material_index: mesh.material_index,
+                        }),
+                );
+            }
+        }
+    }
 
-                    if a >= 0.0 && b >= 0.0 && c >= 0.0 {
-                        let uv = Uv::new(
-                            a * rt.t0.u + b * rt.t1.u + c * rt.t2.u,
-                            a * rt.t0.v + b * rt.t1.v + c * rt.t2.v,
-                        );
+    /// Convert primitives to screen space & bin them into the tiles they overlap.
+    fn bin_primitives(&mut self) {
+        let width = self.framebuffer.width();
+        let height = self.framebuffer.height();
+        let screenspace_mul_vec = Vec3::new(width as f32 / 2.0, height as f32 / 2.0, 1.0);
+        let to_screen = |v: Vec3| (v + Vec3::new(1.0, 1.0, 0.0)) * screenspace_mul_vec;
+        let tiles_x = width.div_ceil(TILE_SIZE);
+
+        self.tiles.iter_mut().for_each(|tile| {
+            tile.triangles.clear();
+            tile.points.clear();
+        });
 
-                        let color = material.sample(uv.u, uv.v);
-                        let cell =
-                            Cell::new_bg(termion::color::Rgb(color.0[0], color.0[1], color.0[2]));
+        for (i, rt) in self.render_triangles.iter_mut().enumerate() {
+            rt.v0 = to_screen(rt.v0);
+            rt.v1 = to_screen(rt.v1);
+            rt.v2 = to_screen(rt.v2);
+            let Some((min_x, min_y, max_x, max_y)) = rt.bounds(width, height) else {
+                continue;
+            };
+            for ty in min_y / TILE_SIZE..=max_y / TILE_SIZE {
+                for tx in min_x / TILE_SIZE..=max_x / TILE_SIZE {
+                    self.tiles[tx + ty * tiles_x].triangles.push(i);
+                }
+            }
+        }
 
-                        drawer.pixel(&cell, px, py);
-                    }
-                });
-        });
+        for (i, rp) in self.render_points.iter_mut().enumerate() {
+            rp.v = to_screen(rp.v);
+            let (px, py) = (rp.v.x as isize, rp.v.y as isize);
+            if px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+                continue;
+            }
+            let (tx, ty) = (px as usize / TILE_SIZE, py as usize / TILE_SIZE);
+            self.tiles[tx + ty * tiles_x].points.push(i);
+        }
+    }
+
+    fn render_inner(&mut self, mut dbg_text: impl std::fmt::Write) -> Result<(), Box<dyn Error>> {
+        let width = self.framebuffer.width();
+        let height = self.framebuffer.height();
+        writeln!(dbg_text, "Out buffer: {}x{}", width, height)?;
+
+        self.process_vertices();
+        self.assemble_primitives();
+        self.bin_primitives();
+
+        // Tiles don't overlap, so they're rasterized in parallel & copied to the framebuffer.
+        let triangles = &self.render_triangles;
+        let points = &self.render_points;
+        let materials = &self.scene.materials;
+        self.tiles
+            .par_iter_mut()
+            .for_each(|tile| tile.rasterize(triangles, points, materials));
+
+        for tile in self.tiles.iter() {
+            for y in 0..tile.height {
+                for x in 0..tile.width {
+                    *self.framebuffer.get_mut_unchecked(tile.x + x, tile.y + y) =
+                        tile.color[x + y * tile.width];
+                }
+            }
+        }
 
         Ok(())
     }
 
+    /// Render a frame, the returned framebuffer is reused by the next frame.
     pub fn render(
         &mut self,
         width: usize,
         height: usize,
-    ) -> Result<(Display, RenderInfo), Box<dyn Error>> {
+    ) -> Result<(&Display, RenderInfo), Box<dyn Error>> {
         let start = std::time::Instant::now();
         let mut dbg_text = String::new();
 
-        let mut display = Display::init_with_size(&BG_COLOR, width, height);
-        let mut drawer = Drawer::new(&mut display);
+        // Tiles cover the framebuffer & write every pixel, so it's only reallocated on resize.
+        if self.framebuffer.width() != width || self.framebuffer.height() != height {
+            self.framebuffer = Display::init_with_size(&BG_COLOR, width, height);
+            self.tiles = (0..height.div_ceil(TILE_SIZE))
+                .flat_map(|ty| (0..width.div_ceil(TILE_SIZE)).map(move |tx| (tx, ty)))
+                .map(|(tx, ty)| {
+                    let (x, y) = (tx * TILE_SIZE, ty * TILE_SIZE);
+                    Tile::new(x, y, TILE_SIZE.min(width - x), TILE_SIZE.min(height - y))
+                })
+                .collect();
+        }
 
-        self.render_inner(&mut drawer, &mut dbg_text)?;
+        self.render_inner(&mut dbg_text)?;
 
         self.render_count += 1;
 
         Ok((
-            display,
+            &self.framebuffer,
             RenderInfo {
                 dbg_text,
                 render_time: start.elapsed(),
                 render_count: self.render_count,
+                bytes_written: 0,
             },
         ))
     }
 }
+
+#[test]
+fn renderer_tests() {
+    // Near red quad first & far green quad second.
+    let scene = Scene::from_quads(&[
+        (10.0, -10.0, Rgb([255, 0, 0])),
+        (10.0, 10.0, Rgb([0, 255, 0])),
+    ]);
+    let mut renderer = Renderer::new(scene);
+    renderer.controller.set_distance(100.0);
+
+    for _ in 0..2 {
+        let (framebuffer, _) = renderer.render(32, 32).unwrap();
+        assert_eq!(framebuffer.get(16, 16).unwrap().bg(), color::Rgb(255, 0, 0));
+        assert_eq!(framebuffer.get(0, 0).unwrap().bg(), BG);
+    }
+
+    // Viewed from behind, the green quad is in front.
+    renderer.controller.grab_move(std::f32::consts::PI, 0.0);
+    let (framebuffer, _) = renderer.render(32, 32).unwrap();
+    assert_eq!(framebuffer.get(16, 16).unwrap().bg(), color::Rgb(0, 255, 0));
+
+    // Quad spans several tiles, including partial ones at the edges.
+    let (framebuffer, _) = renderer.render(70, 70).unwrap();
+    for (x, y) in [(35, 35), (31, 31), (32, 32), (31, 38)] {
+        assert_eq!(framebuffer.get(x, y).unwrap().bg(), color::Rgb(0, 255, 0));
+    }
+    assert_eq!(framebuffer.get(69, 69).unwrap().bg(), BG);
+}
@@ -1,7 +1,7 @@
 #![allow(unused)]
 
 use crate::{
-    material::Material,
+    material::{Material, MaterialGenericColor},
     mesh::Mesh,
     raycast::{Ray, RayIntersection},
 };
@@ -20,6 +20,44 @@ impl Scene {
         }
     }
 
+    /// Scene with a single mesh using a plain white material.
+    pub fn from_mesh(mut mesh: Mesh) -> Self {
+        let mut scene = Self::new();
+        mesh.material_index = 0;
+        scene.meshes.push(mesh);
+        scene
+            .materials
+            .push(Box::new(MaterialGenericColor::new(image::Rgb([
+                255, 255, 255,
+            ]))));
+        scene
+    }
+
+    /// Double sided quads facing the camera, centered on the origin, as (half size, z, color).
+    #[cfg(test)]
+    pub fn from_quads(quads: &[(f32, f32, image::Rgb<u8>)]) -> Self {
+        use crate::math::vector3::Vec3;
+
+        let mut position = Vec::new();
+        let mut color = Vec::new();
+        let mut indices = Vec::new();
+        for (half, z, c) in quads {
+            let i = position.len();
+            position.extend(
+                [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                    .map(|(x, y)| Vec3::new(x * half, y * half, *z)),
+            );
+            color.extend([*c; 4]);
+            indices.extend([
+                (i, i + 1, i + 2),
+                (i, i + 2, i + 3),
+                (i, i + 2, i + 1),
+                (i, i + 3, i + 2),
+            ]);
+        }
+        Self::from_mesh(Mesh::new(0, position, None, indices).with_color(color))
+    }
+
     pub fn intersect(&self, ray: &Ray) -> Option<RayIntersection> {
         self.meshes
             .iter()
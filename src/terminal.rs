@@ -0,0 +1,270 @@
+//! Terminal setup, input events & escape codes, so the rest of the crate doesn't depend on the
+//! terminal library.
+
+use std::{
+    io::{Read, Write},
+    time::Duration,
+};
+
+use crossterm::{cursor, event, execute, queue, terminal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Esc,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseKind {
+    Press(MouseButton),
+    Release(MouseButton),
+    /// Movement while a button is held.
+    Drag(MouseButton),
+    ScrollUp,
+    ScrollDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Key(Key),
+    /// Cell position starting at 0, 0.
+    Mouse {
+        kind: MouseKind,
+        x: usize,
+        y: usize,
+    },
+    /// New size in cells.
+    Resize(usize, usize),
+}
+
+impl Event {
+    fn from_crossterm(event: event::Event) -> Option<Self> {
+        let button = |button: event::MouseButton| match button {
+            event::MouseButton::Left => MouseButton::Left,
+            event::MouseButton::Right => MouseButton::Right,
+            event::MouseButton::Middle => MouseButton::Middle,
+        };
+        match event {
+            event::Event::Key(key) if key.kind != event::KeyEventKind::Release => {
+                let key = match key.code {
+                    event::KeyCode::Char(c) => Key::Char(c),
+                    event::KeyCode::Esc => Key::Esc,
+                    event::KeyCode::Left => Key::Left,
+                    event::KeyCode::Right => Key::Right,
+                    event::KeyCode::Up => Key::Up,
+                    event::KeyCode::Down => Key::Down,
+                    _ => return None,
+                };
+                Some(Event::Key(key))
+            }
+            event::Event::Mouse(mouse) => {
+                let kind = match mouse.kind {
+                    event::MouseEventKind::Down(b) => MouseKind::Press(button(b)),
+                    event::MouseEventKind::Up(b) => MouseKind::Release(button(b)),
+                    event::MouseEventKind::Drag(b) => MouseKind::Drag(button(b)),
+                    event::MouseEventKind::ScrollUp => MouseKind::ScrollUp,
+                    event::MouseEventKind::ScrollDown => MouseKind::ScrollDown,
+                    _ => return None,
+                };
+                Some(Event::Mouse {
+                    kind,
+                    x: mouse.column as usize,
+                    y: mouse.row as usize,
+                })
+            }
+            event::Event::Resize(columns, rows) => {
+                Some(Event::Resize(columns as usize, rows as usize))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Interactive terminal in raw mode on the alternate screen with mouse capture, restored when
+/// dropped.
+pub struct Terminal {
+    stdout: std::io::StdoutLock<'static>,
+}
+
+impl Terminal {
+    pub fn new() -> std::io::Result<Self> {
+        let mut stdout = std::io::stdout().lock();
+        terminal::enable_raw_mode()?;
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            event::EnableMouseCapture,
+        )?;
+        Ok(Self { stdout })
+    }
+
+    /// Wait up to timeout for an input event, events the renderer doesn't use are skipped.
+    pub fn poll_event(&mut self, timeout: Duration) -> std::io::Result<Option<Event>> {
+        let start = std::time::Instant::now();
+        loop {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            if !event::poll(remaining)? {
+                return Ok(None);
+            }
+            if let Some(event) = Event::from_crossterm(event::read()?) {
+                return Ok(Some(event));
+            }
+        }
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(
+            self.stdout,
+            event::DisableMouseCapture,
+            cursor::Show,
+            terminal::LeaveAlternateScreen,
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Raw stdin that returns 0 bytes instead of blocking when no input is available, for reading
+/// terminal query responses before any events are polled.
+pub struct NonBlockingStdin;
+
+impl Read for NonBlockingStdin {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: fd is a valid pollfd & buf is valid for buf.len() bytes.
+        unsafe {
+            if libc::poll(&mut fd, 1, 0) <= 0 || fd.revents & libc::POLLIN == 0 {
+                return Ok(0);
+            }
+            match libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) {
+                n if n < 0 => Err(std::io::Error::last_os_error()),
+                n => Ok(n as usize),
+            }
+        }
+    }
+}
+
+/// Send a query & read the response, for queries that can only be read before any events are
+/// polled. The primary device attributes query is sent after it, which every terminal answers,
+/// so terminals that ignore the query don't wait for the whole timeout.
+pub fn query(
+    mut stdin: impl Read,
+    mut stdout: impl Write,
+    request: &str,
+    timeout: Duration,
+) -> std::io::Result<String> {
+    write!(stdout, "{}\x1B[c", request)?;
+    stdout.flush()?;
+
+    let start = std::time::Instant::now();
+    let mut response: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 64];
+    while start.elapsed() < timeout {
+        let read = stdin.read(&mut buf)?;
+        response.extend_from_slice(&buf[..read]);
+        // Primary device attributes response "\x1B[?...c".
+        let str = String::from_utf8_lossy(&response);
+        if str.find("\x1B[?").is_some_and(|da| str[da..].contains('c')) {
+            break;
+        }
+        if read == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Query cell size in pixels (width, height) with "CSI 16 t", answered with "CSI 6 ; h ; w t".
+pub fn query_cell_size(
+    stdin: impl Read,
+    stdout: impl Write,
+    timeout: Duration,
+) -> std::io::Result<Option<(usize, usize)>> {
+    let response = query(stdin, stdout, "\x1B[16t", timeout)?;
+    Ok(response.split("\x1B[6;").nth(1).and_then(|r| {
+        let (height, width) = r.split_once('t')?.0.split_once(';')?;
+        match (width.parse().ok()?, height.parse().ok()?) {
+            (0, _) | (_, 0) => None,
+            size => Some(size),
+        }
+    }))
+}
+
+/// Cell size in pixels (width, height) from the terminal pixel size, if the terminal reports it.
+pub fn cell_size_pixels() -> Option<(usize, usize)> {
+    let size = terminal::window_size().ok()?;
+    if size.width == 0 || size.height == 0 || size.columns == 0 || size.rows == 0 {
+        return None;
+    }
+    Some((
+        size.width as usize / size.columns as usize,
+        size.height as usize / size.rows as usize,
+    ))
+}
+
+/// Terminal size in cells.
+pub fn size() -> std::io::Result<(usize, usize)> {
+    let (columns, rows) = terminal::size()?;
+    Ok((columns as usize, rows as usize))
+}
+
+/// Terminal size in pixels, not every terminal reports it.
+pub fn size_pixels() -> std::io::Result<(usize, usize)> {
+    let size = terminal::window_size()?;
+    Ok((size.width as usize, size.height as usize))
+}
+
+/// Move the cursor to a cell, starting at 0, 0.
+pub fn move_to(mut out: impl Write, x: usize, y: usize) -> std::io::Result<()> {
+    queue!(out, cursor::MoveTo(x as u16, y as u16))
+}
+
+pub fn clear_all(mut out: impl Write) -> std::io::Result<()> {
+    queue!(out, terminal::Clear(terminal::ClearType::All))
+}
+
+/// Clear from the cursor to the end of the line.
+pub fn clear_line(mut out: impl Write) -> std::io::Result<()> {
+    queue!(out, terminal::Clear(terminal::ClearType::UntilNewLine))
+}
+
+pub fn reset_colors(mut out: impl Write) -> std::io::Result<()> {
+    queue!(out, crossterm::style::ResetColor)
+}
+
+#[test]
+fn query_tests() {
+    let mut response = &b"\x1B[6;20;9t\x1B[?62;c"[..];
+    let size = query_cell_size(&mut response, std::io::sink(), Duration::from_millis(10));
+    assert_eq!(size.unwrap(), Some((9, 20)));
+    let mut response = &b"\x1B[?62;c"[..];
+    let size = query_cell_size(&mut response, std::io::sink(), Duration::from_millis(10));
+    assert_eq!(size.unwrap(), None);
+}
@@ -0,0 +1,144 @@
+//! Animations orbiting the camera 360° around the scene.
+
+use std::{error::Error, fs::File, io::BufWriter, path::Path};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+
+use crate::{
+    headless::{self, CameraPlacement, Size},
+    renderer::Renderer,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct TurntableOptions {
+    pub size: Size,
+    /// Placement of the first frame, the yaw increases every frame.
+    pub camera: CameraPlacement,
+    pub frames: usize,
+    pub fps: u16,
+}
+
+/// Output format, picked from the path extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurntableFormat {
+    Gif,
+    Apng,
+    /// Numbered PNG files inside of a directory.
+    PngSequence,
+}
+
+impl TurntableFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("gif") => TurntableFormat::Gif,
+            Some("png") | Some("apng") => TurntableFormat::Apng,
+            _ => TurntableFormat::PngSequence,
+        }
+    }
+}
+
+/// Render every frame & write it to path, so all frames don't have to be kept in memory.
+pub fn render_turntable(
+    renderer: &mut Renderer,
+    options: TurntableOptions,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if options.frames == 0 || options.fps == 0 {
+        return Err("Turntable needs at least 1 frame & 1 fps".into());
+    }
+    let frames = (0..options.frames).map(|i| {
+        CameraPlacement {
+            yaw: options.camera.yaw + 360.0 * i as f32 / options.frames as f32,
+            ..options.camera
+        }
+        .apply(&mut renderer.controller);
+        headless::render_image(renderer, options.size)
+    });
+
+    match TurntableFormat::from_path(path) {
+        TurntableFormat::Gif => {
+            let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = image::Delay::from_numer_denom_ms(1000, options.fps as u32);
+            for frame in frames {
+                // Frames are drawn over the previous frame, so transparent pixels would keep
+                // the previous frame visible.
+                let mut frame = frame?;
+                frame.pixels_mut().for_each(|p| p.0[3] = 255);
+                encoder.encode_frame(image::Frame::from_parts(frame, 0, 0, delay))?;
+            }
+        }
+        TurntableFormat::Apng => {
+            let mut encoder = png::Encoder::new(
+                BufWriter::new(File::create(path)?),
+                options.size.width as u32,
+                options.size.height as u32,
+            );
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(options.frames as u32, 0)?;
+            encoder.set_frame_delay(1, options.fps)?;
+            let mut writer = encoder.write_header()?;
+            for frame in frames {
+                writer.write_image_data(&frame?)?;
+            }
+            writer.finish()?;
+        }
+        TurntableFormat::PngSequence => {
+            std::fs::create_dir_all(path)?;
+            let digits = options.frames.to_string().len().max(4);
+            for (i, frame) in frames.enumerate() {
+                frame?.save(path.join(format!("frame_{:0digits$}.png", i)))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn turntable_tests() {
+    use image::AnimationDecoder;
+
+    use crate::scene::Scene;
+
+    let white = image::Rgb([255, 255, 255]);
+    let mut renderer = Renderer::new(Scene::from_quads(&[(20.0, 0.0, white)]));
+    let options = TurntableOptions {
+        size: Size {
+            width: 16,
+            height: 12,
+        },
+        camera: CameraPlacement::default(),
+        frames: 3,
+        fps: 10,
+    };
+
+    let dir = std::env::temp_dir().join(format!("turntable_tests_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let gif = dir.join("turntable.gif");
+    render_turntable(&mut renderer, options, &gif).unwrap();
+    let decoder =
+        image::codecs::gif::GifDecoder::new(std::io::BufReader::new(File::open(&gif).unwrap()))
+            .unwrap();
+    assert_eq!(decoder.into_frames().count(), 3);
+
+    let apng = dir.join("turntable.png");
+    render_turntable(&mut renderer, options, &apng).unwrap();
+    let decoder =
+        image::codecs::png::PngDecoder::new(std::io::BufReader::new(File::open(&apng).unwrap()))
+            .unwrap();
+    assert!(decoder.is_apng().unwrap());
+    assert_eq!(decoder.apng().unwrap().into_frames().count(), 3);
+
+    let sequence = dir.join("frames");
+    render_turntable(&mut renderer, options, &sequence).unwrap();
+    assert!(sequence.join("frame_0002.png").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}